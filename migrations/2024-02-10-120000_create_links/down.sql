@@ -0,0 +1,8 @@
+ALTER TABLE stream ADD COLUMN stream_link TEXT NOT NULL DEFAULT '';
+
+UPDATE stream SET stream_link = COALESCE(
+	(SELECT group_concat(url, ',') FROM (SELECT url FROM links WHERE links.event_id = stream.id ORDER BY position)),
+	''
+);
+
+DROP TABLE links;
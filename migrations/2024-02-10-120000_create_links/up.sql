@@ -0,0 +1,21 @@
+CREATE TABLE "links" (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	event_id INTEGER NOT NULL REFERENCES stream(id) ON DELETE CASCADE,
+	url TEXT NOT NULL,
+	position INTEGER NOT NULL,
+	first_seen TIMESTAMP NOT NULL,
+	last_seen TIMESTAMP NOT NULL,
+	UNIQUE(event_id, url)
+);
+
+-- split the comma joined stream_link column into one row per link
+WITH RECURSIVE split(event_id, position, url, rest) AS (
+	SELECT id, -1, '', stream_link || ',' FROM stream WHERE stream_link != ''
+	UNION ALL
+	SELECT event_id, position + 1, substr(rest, 1, instr(rest, ',') - 1), substr(rest, instr(rest, ',') + 1)
+	FROM split WHERE rest != ''
+)
+INSERT OR IGNORE INTO links (event_id, url, position, first_seen, last_seen)
+SELECT event_id, url, position, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP FROM split WHERE position >= 0 AND url != '';
+
+ALTER TABLE stream DROP COLUMN stream_link;
@@ -0,0 +1,5 @@
+ALTER TABLE links DROP COLUMN channel_type;
+ALTER TABLE links DROP COLUMN ad_count;
+ALTER TABLE links DROP COLUMN quality;
+ALTER TABLE links DROP COLUMN language;
+ALTER TABLE links DROP COLUMN streamer;
@@ -0,0 +1,5 @@
+ALTER TABLE links ADD COLUMN streamer TEXT;
+ALTER TABLE links ADD COLUMN language TEXT;
+ALTER TABLE links ADD COLUMN quality TEXT;
+ALTER TABLE links ADD COLUMN ad_count INTEGER;
+ALTER TABLE links ADD COLUMN channel_type TEXT;
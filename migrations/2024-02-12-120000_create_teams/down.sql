@@ -0,0 +1,5 @@
+ALTER TABLE stream DROP COLUMN away_team_id;
+ALTER TABLE stream DROP COLUMN home_team_id;
+
+DROP TABLE team_aliases;
+DROP TABLE teams;
@@ -0,0 +1,23 @@
+CREATE TABLE teams (
+	id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	name TEXT NOT NULL UNIQUE
+);
+
+-- every spelling we have seen of a team, normalized (lowercase, no accents, no look-alike letters)
+CREATE TABLE team_aliases (
+	alias TEXT PRIMARY KEY NOT NULL,
+	team_id INTEGER NOT NULL REFERENCES teams(id)
+);
+
+ALTER TABLE stream ADD COLUMN home_team_id INTEGER REFERENCES teams(id);
+ALTER TABLE stream ADD COLUMN away_team_id INTEGER REFERENCES teams(id);
+
+-- existing events get one team per distinct name, the aliases are added as they are resolved again
+INSERT OR IGNORE INTO teams (name)
+SELECT home FROM stream
+UNION
+SELECT away FROM stream;
+
+UPDATE stream SET
+	home_team_id = (SELECT id FROM teams WHERE teams.name = stream.home),
+	away_team_id = (SELECT id FROM teams WHERE teams.name = stream.away);
@@ -0,0 +1,6 @@
+-- things the scraper wants to remember between runs, like the timezone the pages were read in
+CREATE TABLE scrape_meta (
+	key TEXT PRIMARY KEY NOT NULL,
+	value TEXT NOT NULL,
+	updated_at TIMESTAMP NOT NULL
+);
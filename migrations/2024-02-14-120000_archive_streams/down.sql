@@ -0,0 +1,3 @@
+DROP INDEX stream_archived_at;
+
+ALTER TABLE stream DROP COLUMN archived_at;
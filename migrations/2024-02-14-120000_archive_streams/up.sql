@@ -0,0 +1,4 @@
+-- past events are archived instead of deleted, they are only deleted once the retention period is over
+ALTER TABLE stream ADD COLUMN archived_at TIMESTAMP;
+
+CREATE INDEX stream_archived_at ON stream (archived_at);
@@ -0,0 +1,5 @@
+DROP TABLE link_sightings;
+
+DELETE FROM links WHERE removed_at IS NOT NULL;
+
+ALTER TABLE links DROP COLUMN removed_at;
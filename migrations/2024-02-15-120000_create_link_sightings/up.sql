@@ -0,0 +1,17 @@
+-- links that are no longer on the page are kept and marked as removed
+ALTER TABLE links ADD COLUMN removed_at TIMESTAMP;
+
+-- every stretch of time a link was on the page, a link pulled and put back has two
+CREATE TABLE link_sightings (
+	id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	link_id INTEGER NOT NULL REFERENCES links(id) ON DELETE CASCADE,
+	first_seen TIMESTAMP NOT NULL,
+	last_seen TIMESTAMP NOT NULL,
+	removed_at TIMESTAMP
+);
+
+CREATE INDEX link_sightings_link_id ON link_sightings (link_id);
+
+-- the links we already have were on the page the whole time we know of
+INSERT INTO link_sightings (link_id, first_seen, last_seen)
+SELECT id, first_seen, last_seen FROM links;
@@ -0,0 +1,5 @@
+ALTER TABLE links DROP COLUMN checked_at;
+ALTER TABLE links DROP COLUMN player_type;
+ALTER TABLE links DROP COLUMN final_url;
+ALTER TABLE links DROP COLUMN http_status;
+ALTER TABLE links DROP COLUMN reachable;
@@ -0,0 +1,6 @@
+-- the result of the last time we opened the link, null until it is checked
+ALTER TABLE links ADD COLUMN reachable BOOLEAN;
+ALTER TABLE links ADD COLUMN http_status INTEGER;
+ALTER TABLE links ADD COLUMN final_url TEXT;
+ALTER TABLE links ADD COLUMN player_type TEXT;
+ALTER TABLE links ADD COLUMN checked_at TIMESTAMP;
@@ -0,0 +1,15 @@
+-- one row per scrape, finished_at stays null while it runs (or if it crashed)
+CREATE TABLE scrape_runs (
+	id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	kind TEXT NOT NULL,
+	started_at TIMESTAMP NOT NULL,
+	finished_at TIMESTAMP,
+	events_found INTEGER NOT NULL DEFAULT 0,
+	links_found INTEGER NOT NULL DEFAULT 0,
+	-- json object of sport name to the number of events found
+	sport_counts TEXT NOT NULL DEFAULT '{}',
+	-- json array of error messages
+	errors TEXT NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX scrape_runs_kind_started_at ON scrape_runs (kind, started_at);
@@ -0,0 +1,4 @@
+-- a run can lose a sport or a game and still count, it only fails when it aborted or everything it tried failed
+ALTER TABLE scrape_runs ADD COLUMN succeeded BOOLEAN NOT NULL DEFAULT 0;
+
+UPDATE scrape_runs SET succeeded = 1 WHERE finished_at IS NOT NULL AND errors = '[]';
@@ -0,0 +1 @@
+DROP TABLE mirror_health;
@@ -0,0 +1,16 @@
+-- how every mirror of a sport did, so a dead one is tried last
+-- the last mirror that worked is in scrape_meta, under mirror:<sport>
+CREATE TABLE mirror_health (
+	sport TEXT NOT NULL,
+	url TEXT NOT NULL,
+	successes INTEGER NOT NULL DEFAULT 0,
+	failures INTEGER NOT NULL DEFAULT 0,
+	-- reset by every success
+	consecutive_failures INTEGER NOT NULL DEFAULT 0,
+	last_success TIMESTAMP,
+	last_failure TIMESTAMP,
+	last_error TEXT,
+	-- found on the landing page instead of the config
+	discovered BOOLEAN NOT NULL DEFAULT 0,
+	PRIMARY KEY (sport, url)
+);
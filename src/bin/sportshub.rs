@@ -1,7 +1,18 @@
-use clap::{Parser, Subcommand};
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::bail;
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use diesel::SqliteConnection;
 use scraper::{
-    db::{self, helpers::run_migrations},
+    config::{Config, MEMORY_DATABASE},
+    db::{self, helpers::run_migrations, models::ScrapeReport},
+    fetcher::{ChromeFetcher, Fetcher, HttpFetcher},
+    link_checker::{self, ChromeProber, HttpProber, Prober},
+    scheduler::{self, RunLock},
     scrape,
+    scrape_utils,
+    search,
     web_server_routes,
 };
 
@@ -11,6 +22,51 @@ use scraper::{
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Path of the sqlite database
+    /// (default: $DATABASE_URL or the one in .env, then the config file, then sports.db in the temp directory)
+    /// usage: sportshub --database ./sports.db data info
+    #[clap(long, global = true)]
+    database: Option<String>,
+
+    /// Path of the toml config file
+    /// (default: sportshub.toml if it exists)
+    /// usage: sportshub --config /etc/sportshub.toml serve
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// The utc offset to read the pages in, overrides the config file
+    /// (default: utc)
+    /// usage: sportshub --timezone +01:00 data scrape
+    #[clap(long, global = true, allow_hyphen_values = true)]
+    timezone: Option<String>,
+
+    /// A toml or json file with the sports to scrape, replacing the built-in list
+    /// usage: sportshub --sports-file sports.toml data scrape
+    #[clap(long, global = true)]
+    sports_file: Option<PathBuf>,
+
+    /// Only scrape these sports, can be repeated
+    /// usage: sportshub --sport Football --sport Basketball data scrape
+    #[clap(long = "sport", global = true)]
+    only_sports: Vec<String>,
+
+    /// Don't scrape this sport, can be repeated
+    /// usage: sportshub --disable-sport Darts data scrape
+    #[clap(long = "disable-sport", global = true)]
+    disabled_sports: Vec<String>,
+
+    /// Scrape a sport from another url, as NAME=URL, can be repeated
+    /// usage: sportshub --sport-url Football=https://reddit4.sportshub.stream/ data scrape
+    #[clap(long = "sport-url", global = true, value_parser = parse_sport_url)]
+    sport_urls: Vec<(String, String)>,
+}
+
+fn parse_sport_url(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(name, url)| (name.trim().to_owned(), url.trim().to_owned()))
+        .ok_or(format!("expected NAME=URL, got {}", value))
 }
 
 #[derive(Subcommand)]
@@ -20,6 +76,11 @@ enum Commands {
         #[command(subcommand)]
         data_command: Option<DataCommands>,
     },
+    #[clap(about = "Manage the database")]
+    Db {
+        #[command(subcommand)]
+        db_command: Option<DbCommands>,
+    },
     #[clap(about = "Run the web server")]
     Serve {
         /// port to run the server on  
@@ -40,7 +101,15 @@ enum Commands {
         /// usage: sportshub serve -F -s
         #[clap(short = 'F', long = "full-refresh")]
         full_refresh: bool,
+
+        /// Keep scraping in the background on the `[schedule]` of the config file
+        /// (default: false)
+        /// usage: sportshub serve -S
+        #[clap(short = 'S', long = "schedule")]
+        schedule: bool,
     },
+    #[clap(about = "Scrape on the schedule of the config file, without the web server")]
+    Daemon {},
 }
 
 #[derive(Subcommand, Clone)]
@@ -52,6 +121,18 @@ enum DataCommands {
         /// usage: sportshub scrape -H false
         #[clap(short = 'H', long = "headless", default_value = "true")]
         headless: bool,
+
+        /// How to download the pages, plain http doesn't need chromium installed
+        /// (default: http)
+        /// usage: sportshub scrape -f chrome
+        #[clap(short = 'f', long = "fetcher", value_enum, default_value = "http")]
+        fetcher: FetcherKind,
+
+        /// How many times a sport page is tried before giving up on the sport
+        /// (default: `attempts` in the `[retry]` table of the config, 3)
+        /// usage: sportshub data scrape -r 5
+        #[clap(short = 'r', long = "retries")]
+        attempts: Option<u32>,
     },
     #[clap(about = "Update the database with latest games links")]
     Update {
@@ -68,67 +149,543 @@ enum DataCommands {
         #[clap(short = 'H', long = "headless")]
         headless: bool,
     },
+    #[clap(about = "Check that the stream links still work")]
+    Check {
+        /// How many links to check at the same time
+        /// (default: 10)
+        /// usage: sportshub data check -w 20
+        #[clap(short = 'w', long = "workers", default_value_t = link_checker::DEFAULT_WORKERS)]
+        workers: usize,
+
+        /// How many seconds a link has to answer
+        /// (default: 10)
+        /// usage: sportshub data check -T 5
+        #[clap(short = 'T', long = "timeout", default_value = "10")]
+        timeout: u64,
+
+        /// Skip the links checked less than this many minutes ago
+        /// (default: 30)
+        /// usage: sportshub data check -m 0
+        #[clap(short = 'm', long = "max-age", default_value = "30")]
+        max_age: i64,
+
+        /// How to open the links, chrome runs the javascript of embed pages
+        /// (default: http)
+        /// usage: sportshub data check -f chrome
+        #[clap(short = 'f', long = "fetcher", value_enum, default_value = "http")]
+        fetcher: FetcherKind,
+
+        /// Whether to run the browser in headless mode, when using chrome
+        #[clap(short = 'H', long = "headless")]
+        headless: bool,
+    },
+    #[clap(about = "Save a live page as a test fixture")]
+    Fixture {
+        /// The page to save
+        url: String,
+
+        /// Where to save the html
+        /// usage: sportshub data fixture https://reddit3.sportshub.stream/ tests/fixtures/schedule_football.html
+        output: PathBuf,
+
+        /// How to download the page
+        /// (default: http)
+        #[clap(short = 'f', long = "fetcher", value_enum, default_value = "http")]
+        fetcher: FetcherKind,
+
+        /// Whether to run the browser in headless mode, when using chrome
+        #[clap(short = 'H', long = "headless")]
+        headless: bool,
+    },
+    #[clap(about = "Search the events by team name")]
+    Search {
+        /// The team to look for, case, accents and typos don't matter
+        /// usage: sportshub data search "man utd"
+        query: String,
+
+        /// The lowest match score to show, from 0 to 1
+        /// (default: 0.8)
+        /// usage: sportshub data search "man utd" -t 0.9
+        #[clap(short = 't', long = "threshold", default_value_t = search::DEFAULT_THRESHOLD)]
+        threshold: f64,
+    },
+    #[clap(about = "Add another name for a team")]
+    Alias {
+        /// The canonical name of the team
+        team: String,
+
+        /// The other name, events with it are shown as this team from now on
+        /// usage: sportshub data alias "Manchester United" "Man Utd"
+        alias: String,
+    },
+    #[clap(about = "List the latest scrapes")]
+    History {
+        /// How many scrapes to show
+        /// (default: 20)
+        /// usage: sportshub data history -n 50
+        #[clap(short = 'n', long = "limit", default_value = "20")]
+        limit: i64,
+    },
+    #[clap(about = "List the sports and where they are scraped from")]
+    Sports {},
+    #[clap(about = "List how the mirrors of the sports did")]
+    Mirrors {
+        /// Only the mirrors of this sport
+        /// usage: sportshub data mirrors Football
+        sport: Option<String>,
+    },
     #[clap(about = "Get the info about the current database")]
     Info {},
-    #[clap(about = "Delete all past streams")]
-    Clear {},
+    #[clap(about = "List the archived events")]
+    Archive {
+        /// Only the events that started on or after this day
+        /// usage: sportshub data archive --from 2024-01-01
+        #[clap(long)]
+        from: Option<NaiveDate>,
+
+        /// Only the events that started on or before this day
+        /// usage: sportshub data archive --from 2024-01-01 --to 2024-01-31
+        #[clap(long)]
+        to: Option<NaiveDate>,
+    },
+    #[clap(about = "Archive all the current events")]
+    Clear {
+        /// Delete every event and link for good instead of archiving them
+        /// usage: sportshub data clear --purge
+        #[clap(long)]
+        purge: bool,
+    },
 }
 
-#[rocket::main]
-async fn main() {
-    let mut conn = db::helpers::establish_connection().unwrap();
+#[derive(Subcommand, Clone)]
+enum DbCommands {
+    #[clap(about = "Apply the pending migrations")]
+    Migrate {},
+    #[clap(about = "Archive the finished events and delete the old archived ones, run it on a schedule")]
+    Prune {},
+    #[clap(about = "Show the migrations and the number of events")]
+    Status {},
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum FetcherKind {
+    /// plain http requests
+    Http,
+    /// headless_chrome tab
+    Chrome,
+}
+
+impl FetcherKind {
+    fn create(self, headless: bool, offset: FixedOffset) -> anyhow::Result<Box<dyn Fetcher>> {
+        Ok(match self {
+            FetcherKind::Http => Box::new(HttpFetcher::new().with_utc_offset(offset)),
+            FetcherKind::Chrome => Box::new(ChromeFetcher::new(headless, offset)?),
+        })
+    }
+
+    fn create_prober(self, headless: bool, timeout: Duration) -> anyhow::Result<Box<dyn Prober>> {
+        Ok(match self {
+            FetcherKind::Http => Box::new(HttpProber::new(timeout)),
+            FetcherKind::Chrome => Box::new(ChromeProber::new(headless, timeout)?),
+        })
+    }
+}
+
+/// Open the database, commands that write to it bring it up to date first
+/// while read only ones refuse to run on a database with pending migrations, or one that doesn't exist
+fn open_database(database_url: &str, config: &Config, migrate: bool) -> anyhow::Result<SqliteConnection> {
+    // sqlite creates a missing database when opening it, a read only command would leave an empty file behind
+    if !migrate && !db::helpers::database_path(database_url).exists() {
+        bail!("there is no database at {}, run `sportshub db migrate` first", database_url);
+    }
+
+    let mut conn = db::helpers::establish_connection(database_url)?;
+
+    if migrate {
+        run_migrations(&mut conn)?;
+        db::helpers::seed_teams(&mut conn, &config.team_aliases()?)?;
+    } else {
+        let pending = db::helpers::pending_migrations(&mut conn)?;
+
+        if !pending.is_empty() {
+            bail!(
+                "the database at {} has {} pending migrations, run `sportshub db migrate` first",
+                database_url,
+                pending.len()
+            );
+        }
+    }
+
+    Ok(conn)
+}
+
+/// Take the lock shared by every scrape run, so a manual run doesn't overlap with the daemon
+fn lock_runs(database_url: &str, config: &Config) -> anyhow::Result<RunLock> {
+    let path = config.lock_file(database_url);
+
+    match RunLock::acquire(&path)? {
+        Some(lock) => Ok(lock),
+        None => bail!("another scrape is running, {} is locked", path.display()),
+    }
+}
+
+/// Print what a scrape couldn't do, it's also saved with the run, see `sportshub data history`
+fn print_failures(report: &ScrapeReport) {
+    if report.errors.is_empty() {
+        return;
+    }
 
-    run_migrations(&mut db::helpers::establish_connection().unwrap(), &mut conn).unwrap();
+    eprintln!("{} failures:", report.errors.len());
+    for failure in &report.errors {
+        eprintln!("    {}", failure);
+    }
+}
 
+#[rocket::main]
+async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
+    // DATABASE_URL can be set in a .env file, like for the diesel cli
+    if let Err(e) = dotenvy::dotenv() {
+        if !e.not_found() {
+            bail!("could not read .env: {}", e);
+        }
+    }
+
+    let mut config = Config::load(cli.config.as_deref())?;
+    if cli.timezone.is_some() {
+        config.timezone = cli.timezone.clone();
+    }
+    if cli.sports_file.is_some() {
+        config.sports.file = cli.sports_file.clone();
+    }
+    if !cli.only_sports.is_empty() {
+        config.sports.only = Some(cli.only_sports.clone());
+    }
+    config.sports.disabled.extend(cli.disabled_sports.iter().cloned());
+    config.sports.urls.extend(cli.sport_urls.iter().cloned());
+
+    // the scrapes, the api and the event durations all use the same sports
+    let catalogue = config.sport_catalogue()?;
+
+    let database_url = config.database_url(cli.database.as_deref());
+
+    // every connection to :memory: gets its own empty database, what the scrape writes the server would never see
+    if database_url == MEMORY_DATABASE {
+        bail!("an in-memory database can't be shared between connections, use a file");
+    }
+    let offset = config.utc_offset()?;
+
     match cli.command {
         Some(Commands::Data { data_command }) => {
             match data_command {
-                Some(DataCommands::Scrape { headless }) => {
-                    scrape::scrape_events(headless).unwrap();
+                Some(DataCommands::Scrape {
+                    headless,
+                    fetcher,
+                    attempts,
+                }) => {
+                    open_database(&database_url, &config, true)?;
+                    let _lock = lock_runs(&database_url, &config)?;
+                    let fetcher = fetcher.create(headless, offset)?;
+
+                    let mut retry = config.retry.clone();
+                    if let Some(attempts) = attempts {
+                        retry.attempts = attempts;
+                    }
+
+                    let report = scrape::scrape_events(
+                        &database_url,
+                        fetcher.as_ref(),
+                        &catalogue.enabled(),
+                        &retry,
+                        config.sports.landing_page.as_deref(),
+                    )?;
+                    println!("Found {} events", report.events_found());
+                    print_failures(&report);
+                }
+                Some(DataCommands::Fixture {
+                    url,
+                    output,
+                    fetcher,
+                    headless,
+                }) => {
+                    let fetcher = fetcher.create(headless, offset)?;
+
+                    let written = scrape_utils::record_fixture(fetcher.as_ref(), &url, &output)?;
+                    println!("Saved {} bytes to {}", written, output.display());
                 }
                 Some(DataCommands::Update { tabs, headless }) => {
-                    scrape::update_streams(tabs as usize, headless).unwrap();
+                    open_database(&database_url, &config, true)?;
+                    let _lock = lock_runs(&database_url, &config)?;
+
+                    let report = scrape::update_streams(&database_url, &catalogue, tabs as usize, headless)?;
+                    println!("Found {} links", report.links_found);
+                    print_failures(&report);
+                }
+                Some(DataCommands::Check {
+                    workers,
+                    timeout,
+                    max_age,
+                    fetcher,
+                    headless,
+                }) => {
+                    let mut conn = open_database(&database_url, &config, true)?;
+                    let _lock = lock_runs(&database_url, &config)?;
+                    let prober = fetcher.create_prober(headless, Duration::from_secs(timeout))?;
+
+                    let now = Utc::now().naive_utc();
+                    let checked_before = now - chrono::Duration::minutes(max_age);
+
+                    let summary = link_checker::check_links(&mut conn, prober.as_ref(), workers, checked_before, now)?;
+                    println!(
+                        "Checked {} links: {} reachable, {} dead, {} can't be checked, {} failed",
+                        summary.checked, summary.reachable, summary.dead, summary.unchecked, summary.failed
+                    );
                 }
                 Some(DataCommands::Info {}) => {
-                    let streams = db::helpers::get_streams(&mut conn).unwrap();
+                    let mut conn = open_database(&database_url, &config, false)?;
+
+                    let streams = db::helpers::get_streams(&mut conn)?;
                     println!("Total events: {}", streams.len());
-                    let empty_streams = db::helpers::get_empty_streams(&mut conn).unwrap();
+                    let empty_streams = db::helpers::get_empty_streams(&mut conn)?;
                     println!("Empty events: {}", empty_streams.len());
-                    let linked_streams = db::helpers::get_linked_streams(&mut conn).unwrap();
+                    let linked_streams = db::helpers::get_linked_streams(&mut conn)?;
                     println!("Linked events: {}", linked_streams.len());
+                    let archived_streams = db::helpers::get_archived_streams(&mut conn, None, None)?;
+                    println!("Archived events: {}", archived_streams.len());
+
+                    let total_links = db::helpers::count_links(&mut conn)?;
+
+                    println!("Total streams: {}", total_links);
+
+                    let teams = db::helpers::get_teams(&mut conn)?;
+                    println!("Known teams: {}", teams.len());
+
+                    let offset = db::helpers::get_meta(&mut conn, db::helpers::META_UTC_OFFSET)?;
+                    println!("Last read in: UTC{}", offset.as_deref().unwrap_or("?"));
+                }
+                Some(DataCommands::History { limit }) => {
+                    let mut conn = open_database(&database_url, &config, false)?;
+
+                    for run in db::helpers::get_scrape_runs(&mut conn, limit)? {
+                        let duration = run
+                            .finished_at
+                            .map(|t| format!("{}s", (t - run.started_at).num_seconds()))
+                            .unwrap_or("running".to_owned());
 
-                    let mut total_links = 0;
+                        println!(
+                            "{}  {:<6} {:>8}  {} events  {} links  {}",
+                            run.started_at,
+                            run.kind,
+                            duration,
+                            run.events_found,
+                            run.links_found,
+                            if run.is_success() { "ok" } else { "failed" }
+                        );
 
-                    for stream in linked_streams {
-                        if stream.stream_link != "" {
-                            total_links += stream.stream_link.split(",").count();
+                        for error in run.errors() {
+                            println!("    {}", error);
                         }
                     }
+                }
+                Some(DataCommands::Sports {}) => {
+                    for sport in catalogue.iter() {
+                        println!(
+                            "{:<18} {:>4} min  {:<8}  {}",
+                            sport.name,
+                            sport.duration,
+                            if sport.enabled { "enabled" } else { "disabled" },
+                            sport.url
+                        );
+                    }
+                }
+                Some(DataCommands::Mirrors { sport }) => {
+                    let mut conn = open_database(&database_url, &config, false)?;
 
-                    println!("Total streams: {}", total_links);
+                    for mirror in db::helpers::get_mirror_health(&mut conn, sport.as_deref())? {
+                        let working = db::helpers::get_working_mirror(&mut conn, &mirror.sport)?;
+
+                        println!(
+                            "{:<18} {:<45} {:>4} ok {:>4} failed  {:<10} {}",
+                            mirror.sport,
+                            mirror.url,
+                            mirror.successes,
+                            mirror.failures,
+                            if working.as_deref() == Some(&*mirror.url) {
+                                "working"
+                            } else if mirror.discovered {
+                                "discovered"
+                            } else {
+                                ""
+                            },
+                            mirror.last_error.as_deref().unwrap_or("")
+                        );
+                    }
+                }
+                Some(DataCommands::Search { query, threshold }) => {
+                    let mut conn = open_database(&database_url, &config, false)?;
+                    let matches = db::helpers::search_streams(&mut conn, &query, threshold)?;
+
+                    if matches.is_empty() {
+                        println!("No events found for {}", query);
+                    }
+
+                    for (s, score) in matches {
+                        println!(
+                            "{:.2}  {} - {}  ({}, {})  {}",
+                            score, s.home, s.away, s.league, s.sport, s.start_time
+                        );
+                    }
+                }
+                Some(DataCommands::Alias { team, alias }) => {
+                    let mut conn = open_database(&database_url, &config, true)?;
+                    let team = db::helpers::add_team_alias(&mut conn, &team, &alias)?;
+                    println!("{} is now {} (team {})", alias, team.name, team.id);
+                }
+                Some(DataCommands::Archive { from, to }) => {
+                    let mut conn = open_database(&database_url, &config, false)?;
+
+                    let from = from.map(|d| NaiveDateTime::new(d, NaiveTime::MIN));
+                    let to = to.map(|d| NaiveDateTime::new(d, NaiveTime::from_hms_opt(23, 59, 59).unwrap()));
+
+                    let archived = db::helpers::get_archived_streams(&mut conn, from, to)?;
+                    let archived = db::helpers::attach_links(&mut conn, &catalogue, archived)?;
+
+                    for s in &archived {
+                        println!(
+                            "{}  {} - {}  ({}, {})  {} links",
+                            s.stream.start_time,
+                            s.stream.home,
+                            s.stream.away,
+                            s.stream.league,
+                            s.stream.sport,
+                            s.links.len()
+                        );
+                    }
+
+                    let linked = archived.iter().filter(|s| !s.links.is_empty()).count();
+                    println!("Archived events: {}, with links: {}", archived.len(), linked);
                 }
-                Some(DataCommands::Clear {}) => {
-                    db::helpers::delete_all_streams(&mut conn).unwrap();
+                Some(DataCommands::Clear { purge }) => {
+                    let mut conn = open_database(&database_url, &config, true)?;
+
+                    if purge {
+                        let deleted = db::helpers::delete_all_streams(&mut conn)?;
+                        println!("Deleted {} events", deleted);
+                    } else {
+                        let archived = db::helpers::archive_all_streams(&mut conn, Utc::now().naive_utc())?;
+                        println!("Archived {} events", archived);
+                    }
                 }
                 None => {
                     println!("use sportshub data -h for help");
                 }
             }
         }
+        Some(Commands::Db { db_command }) => match db_command {
+            Some(DbCommands::Migrate {}) => {
+                let mut conn = db::helpers::establish_connection(&database_url)?;
+
+                let applied = run_migrations(&mut conn)?;
+                db::helpers::seed_teams(&mut conn, &config.team_aliases()?)?;
+
+                if applied.is_empty() {
+                    println!("{} is up to date", database_url);
+                }
+
+                for version in applied {
+                    println!("Applied {}", version);
+                }
+            }
+            Some(DbCommands::Prune {}) => {
+                let mut conn = open_database(&database_url, &config, false)?;
+
+                let now = Utc::now().naive_utc();
+                let report = db::helpers::prune_streams(&mut conn, &catalogue, now, config.retention())?;
+                println!("Archived {} events, deleted {} archived events", report.archived, report.purged);
+            }
+            Some(DbCommands::Status {}) => {
+                println!("Database: {}", database_url);
+
+                if !db::helpers::database_path(&database_url).exists() {
+                    println!("The database doesn't exist yet, run `sportshub db migrate` to create it");
+                    return Ok(());
+                }
+
+                let mut conn = db::helpers::establish_connection(&database_url)?;
+
+                let applied = db::helpers::applied_migrations(&mut conn)?;
+                println!("Applied migrations: {}", applied.len());
+
+                let pending = db::helpers::pending_migrations(&mut conn)?;
+                println!("Pending migrations: {}", pending.len());
+                for name in &pending {
+                    println!("  {}", name);
+                }
+
+                // the tables might not exist yet
+                if pending.is_empty() {
+                    println!("Current events: {}", db::helpers::get_streams(&mut conn)?.len());
+                    println!(
+                        "Archived events: {}",
+                        db::helpers::get_archived_streams(&mut conn, None, None)?.len()
+                    );
+                }
+
+                match config.retention_days {
+                    Some(days) => println!("Retention: {} days", days),
+                    None => println!("Retention: forever"),
+                }
+            }
+            None => {
+                println!("use sportshub db -h for help");
+            }
+        },
         Some(Commands::Serve {
             port,
             silent,
             full_refresh,
+            schedule,
         }) => {
+            open_database(&database_url, &config, true)?;
+
             if full_refresh {
-                scrape::start_scraping(10, true).unwrap();
+                let _lock = lock_runs(&database_url, &config)?;
+                let report = scrape::start_scraping(
+                    &database_url,
+                    &catalogue,
+                    10,
+                    true,
+                    offset,
+                    &config.retry,
+                    config.sports.landing_page.as_deref(),
+                )?;
+                print_failures(&report);
+            }
+
+            if schedule {
+                let database_url = database_url.clone();
+                let config = config.clone();
+                let catalogue = catalogue.clone();
+
+                std::thread::spawn(move || {
+                    if let Err(e) = scheduler::run_forever(&database_url, &config, &catalogue) {
+                        eprintln!("the scheduler stopped: {}", e);
+                    }
+                });
             }
-            web_server_routes::run(port, silent).await.unwrap();
+
+            web_server_routes::run(&database_url, catalogue, port, silent).await?;
+        }
+        Some(Commands::Daemon {}) => {
+            open_database(&database_url, &config, true)?;
+
+            scheduler::run_forever(&database_url, &config, &catalogue)?;
         }
         None => {
             println!("use sportshub -h for help");
         }
     }
+
+    Ok(())
 }
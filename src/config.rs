@@ -0,0 +1,308 @@
+//! Settings that can be read from a toml config file.
+//!
+//! The config file is optional, it is read from `--config <path>` or from
+//! `sportshub.toml` in the current directory if it exists.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Error;
+use chrono::{FixedOffset, Offset, Utc};
+use serde::Deserialize;
+
+use crate::{
+    constants::sports::{self, Catalogue, Sport},
+    retry::RetryPolicy,
+    teams::{self, TeamAliases},
+};
+
+/// The config file we look for when `--config` isn't passed
+pub const DEFAULT_CONFIG_FILE: &str = "sportshub.toml";
+
+/// An in-memory database, every connection gets its own empty one so it's only meant for the tests
+pub const MEMORY_DATABASE: &str = ":memory:";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Path of the sqlite database
+    pub database: Option<String>,
+    /// The utc offset the pages are read in, like `+01:00` (default: utc).
+    /// It's a fixed offset, it doesn't follow daylight saving time
+    pub timezone: Option<String>,
+    /// Extra team aliases as `[[teams]]` tables, on top of the bundled ones
+    pub teams: Vec<TeamAliases>,
+    /// How many days archived events are kept before being deleted (default: forever)
+    pub retention_days: Option<u32>,
+    /// How often the daemon scrapes, as a `[schedule]` table
+    pub schedule: ScheduleConfig,
+    /// How a page that fails to load is tried again, as a `[retry]` table
+    pub retry: RetryPolicy,
+    /// Which sports are scraped and from where, as a `[sports]` table
+    pub sports: SportsConfig,
+}
+
+/// Changes to the sports we scrape, on top of the built-in list or of a catalogue file
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SportsConfig {
+    /// A toml (`[[sports]]` tables) or json (an array) file replacing the built-in list
+    pub file: Option<PathBuf>,
+    /// Only scrape these sports, by name (default: every enabled sport)
+    pub only: Option<Vec<String>>,
+    /// Don't scrape these sports, by name
+    pub disabled: Vec<String>,
+    /// Scrape these sports from another url, by name, for when a mirror moves
+    pub urls: BTreeMap<String, String>,
+    /// More mirrors of these sports, by name, tried when the url doesn't work
+    pub mirrors: BTreeMap<String, Vec<String>>,
+    /// A page linking to the current mirrors, like `https://sportshub.stream/`,
+    /// they are tried before the configured ones (default: no discovery)
+    pub landing_page: Option<String>,
+}
+
+/// When the daemon runs its jobs, every interval is in minutes
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// How often the events of every sport are scraped (default: 60)
+    pub events_minutes: u32,
+    /// How often the links of the events without any are scraped (default: 15)
+    pub links_minutes: u32,
+    /// How often the stream links are checked (default: 30)
+    pub checks_minutes: u32,
+    /// The links are scraped and checked this often when an event is about to start (default: 5)
+    pub kickoff_minutes: u32,
+    /// How long before a kickoff the links are scraped more often (default: 30)
+    pub kickoff_window_minutes: u32,
+    /// How often the finished events are archived, and the old archived ones deleted (default: 60)
+    pub prune_minutes: u32,
+    /// How many tabs scrape the links at the same time (default: 10)
+    pub tabs: usize,
+    /// The file that stops two runs from scraping at the same time
+    /// (default: the database path with `.lock` appended)
+    pub lock_file: Option<PathBuf>,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        ScheduleConfig {
+            events_minutes: 60,
+            links_minutes: 15,
+            checks_minutes: 30,
+            kickoff_minutes: 5,
+            kickoff_window_minutes: 30,
+            prune_minutes: 60,
+            tabs: 10,
+            lock_file: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config from `path`, or from `sportshub.toml` if no path is given.
+    /// A missing default file just gives the default config, a missing explicit file is an error.
+    ///
+    /// # Examples
+    /// ```
+    /// use scraper::config::Config;
+    ///
+    /// let config = Config::load(None).unwrap();
+    /// ```
+    pub fn load(path: Option<&Path>) -> Result<Config, Error> {
+        let path = match path {
+            Some(p) => p.to_path_buf(),
+            None => {
+                let default = PathBuf::from(DEFAULT_CONFIG_FILE);
+
+                if !default.exists() {
+                    return Ok(Config::default());
+                }
+
+                default
+            }
+        };
+
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("could not read config {}: {}", path.display(), e))?;
+
+        Config::parse(&contents)
+    }
+
+    /// Parse a config from the contents of a toml file
+    ///
+    /// # Examples
+    /// ```
+    /// use scraper::config::Config;
+    ///
+    /// let config = Config::parse(r#"database = "/var/lib/sportshub/sports.db""#).unwrap();
+    /// assert_eq!(config.database.as_deref(), Some("/var/lib/sportshub/sports.db"));
+    /// ```
+    pub fn parse(contents: &str) -> Result<Config, Error> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Where the database is, in order of priority:
+    /// the `--database` flag, the `DATABASE_URL` env var, the config file,
+    /// and finally `sports.db` in the temp directory.
+    ///
+    /// # Arguments
+    /// * `flag` - The value of the `--database` flag, if it was passed
+    ///
+    /// # Examples
+    /// ```
+    /// use scraper::config::Config;
+    ///
+    /// let config = Config::default();
+    /// assert_eq!(config.database_url(Some(":memory:")), ":memory:");
+    /// ```
+    pub fn database_url(&self, flag: Option<&str>) -> String {
+        if let Some(url) = flag {
+            return url.to_string();
+        }
+
+        if let Ok(url) = std::env::var("DATABASE_URL") {
+            if !url.is_empty() {
+                return url;
+            }
+        }
+
+        if let Some(url) = &self.database {
+            return url.clone();
+        }
+
+        format!("{}/sports.db", std::env::temp_dir().display())
+    }
+
+    /// Every team alias we know of, the bundled ones first so the config file can override them
+    pub fn team_aliases(&self) -> Result<Vec<TeamAliases>, Error> {
+        let mut all = teams::bundled_teams()?;
+        all.extend(self.teams.iter().cloned());
+
+        Ok(all)
+    }
+
+    /// The utc offset the times on the pages are shown in, from the `timezone` setting
+    ///
+    /// # Examples
+    /// ```
+    /// use scraper::config::Config;
+    ///
+    /// let config = Config::parse(r#"timezone = "+01:00""#).unwrap();
+    /// assert_eq!(config.utc_offset().unwrap().local_minus_utc(), 3600);
+    ///
+    /// assert_eq!(Config::default().utc_offset().unwrap().local_minus_utc(), 0);
+    /// ```
+    pub fn utc_offset(&self) -> Result<FixedOffset, Error> {
+        match self.timezone.as_deref().map(str::trim) {
+            None | Some("UTC") | Some("utc") | Some("Z") => Ok(Utc.fix()),
+            Some(offset) => offset
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid timezone {}, expected an offset like +01:00: {}", offset, e)),
+        }
+    }
+
+    /// How long archived events are kept, `None` keeps them forever
+    ///
+    /// # Examples
+    /// ```
+    /// use scraper::config::Config;
+    ///
+    /// let config = Config::parse("retention_days = 30").unwrap();
+    /// assert_eq!(config.retention(), Some(chrono::Duration::days(30)));
+    ///
+    /// assert_eq!(Config::default().retention(), None);
+    /// ```
+    pub fn retention(&self) -> Option<chrono::Duration> {
+        self.retention_days.map(|days| chrono::Duration::days(days as i64))
+    }
+
+    /// The lock file of the scrape runs on `database_url`, see [`ScheduleConfig::lock_file`]
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::PathBuf;
+    ///
+    /// use scraper::config::Config;
+    ///
+    /// let config = Config::default();
+    /// assert_eq!(config.lock_file("/var/lib/sports.db"), PathBuf::from("/var/lib/sports.db.lock"));
+    /// ```
+    pub fn lock_file(&self, database_url: &str) -> PathBuf {
+        if let Some(path) = &self.schedule.lock_file {
+            return path.clone();
+        }
+
+        if database_url == MEMORY_DATABASE {
+            return std::env::temp_dir().join("sportshub.lock");
+        }
+
+        PathBuf::from(format!("{}.lock", database_url))
+    }
+
+    /// The sports we know of, from the catalogue file or the built-in list, with the `[sports]` changes applied.
+    /// Naming a sport that isn't in the catalogue is an error, so a typo doesn't go unnoticed.
+    ///
+    /// # Examples
+    /// ```
+    /// use scraper::config::Config;
+    ///
+    /// let config = Config::parse(
+    ///     r#"
+    ///     [sports]
+    ///     disabled = ["Darts"]
+    ///     urls = { Football = "https://reddit4.sportshub.stream/" }
+    ///     "#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let sports = config.sport_catalogue().unwrap();
+    /// let football = sports.iter().find(|s| s.name == "Football").unwrap();
+    /// assert_eq!(football.url, "https://reddit4.sportshub.stream/");
+    /// assert!(!sports.iter().find(|s| s.name == "Darts").unwrap().enabled);
+    ///
+    /// assert!(Config::parse("[sports]\ndisabled = [\"Quidditch\"]").unwrap().sport_catalogue().is_err());
+    /// ```
+    pub fn sport_catalogue(&self) -> Result<Catalogue, Error> {
+        let mut catalogue = match &self.sports.file {
+            Some(path) => sports::load_catalogue(path)?,
+            None => sports::SPORTS.to_vec(),
+        };
+
+        let known = |catalogue: &[Sport], name: &str| {
+            catalogue
+                .iter()
+                .position(|s| s.name == name)
+                .ok_or(anyhow::anyhow!("unknown sport {}, see `sportshub data sports`", name))
+        };
+
+        for (name, url) in &self.sports.urls {
+            let i = known(&catalogue, name)?;
+            catalogue[i].url = url.clone().into();
+        }
+
+        for (name, mirrors) in &self.sports.mirrors {
+            let i = known(&catalogue, name)?;
+            catalogue[i].mirrors.extend(mirrors.iter().cloned());
+        }
+
+        if let Some(only) = &self.sports.only {
+            for name in only {
+                known(&catalogue, name)?;
+            }
+
+            for sport in catalogue.iter_mut() {
+                sport.enabled &= only.iter().any(|n| *n == sport.name);
+            }
+        }
+
+        for name in &self.sports.disabled {
+            let i = known(&catalogue, name)?;
+            catalogue[i].enabled = false;
+        }
+
+        Ok(Catalogue::new(catalogue))
+    }
+}
@@ -1,53 +1,248 @@
+//! The sports we scrape. The built-in list is the default catalogue,
+//! it can be replaced by a toml or json file and changed from the config or the cli,
+//! see [`crate::config::SportsConfig`].
+
+use std::{borrow::Cow, ops::Deref, path::Path};
+
+use anyhow::Error;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// How long an event lasts when we don't know better, in minutes
+pub const DEFAULT_DURATION: u32 = 180;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Sport {
-    pub name: &'static str,
-    pub url: &'static str,
+    pub name: Cow<'static, str>,
+    pub url: Cow<'static, str>,
+    /// How long an event of this sport usually lasts, in minutes, breaks included
+    #[serde(default = "default_duration")]
+    pub duration: u32,
+    /// Whether we scrape it, a disabled sport keeps its duration for the events we already have
+    #[serde(default = "default_enabled", skip_serializing)]
+    pub enabled: bool,
+    /// Other urls of the same page, tried in order when `url` doesn't work
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
 }
+
+fn default_duration() -> u32 {
+    DEFAULT_DURATION
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
 impl Sport {
     pub const fn new(name: &'static str, url: &'static str) -> Sport {
-        Sport { name, url }
+        Sport {
+            name: Cow::Borrowed(name),
+            url: Cow::Borrowed(url),
+            duration: DEFAULT_DURATION,
+            enabled: true,
+            mirrors: Vec::new(),
+        }
+    }
+
+    /// The same sport, with events lasting `minutes`
+    pub const fn lasting(mut self, minutes: u32) -> Sport {
+        self.duration = minutes;
+        self
+    }
+
+    /// Every url the page of this sport is at, `url` first, without duplicates
+    ///
+    /// # Example
+    /// ```
+    /// use scraper::constants::sports::SOCCER;
+    ///
+    /// let mut soccer = SOCCER.clone();
+    /// soccer.mirrors = vec!["https://reddit4.sportshub.stream/".to_string(), SOCCER.url.to_string()];
+    ///
+    /// assert_eq!(soccer.urls(), vec!["https://reddit3.sportshub.stream/", "https://reddit4.sportshub.stream/"]);
+    /// ```
+    pub fn urls(&self) -> Vec<&str> {
+        let mut urls = vec![self.url.as_ref()];
+
+        for mirror in &self.mirrors {
+            if !urls.contains(&mirror.as_str()) {
+                urls.push(mirror);
+            }
+        }
+
+        urls
+    }
+}
+
+/// Every sport we know of, the disabled ones too. The built-in [`SPORTS`] by default,
+/// the one in use comes from [`crate::config::Config::sport_catalogue`] and is passed to whatever needs it
+///
+/// # Example
+/// ```
+/// use scraper::constants::sports::{Catalogue, Sport};
+///
+/// let catalogue = Catalogue::new(vec![Sport::new("Kabaddi", "https://kabaddi.sportshub.stream/").lasting(50)]);
+///
+/// assert_eq!(catalogue.enabled()[0].name, "Kabaddi");
+/// assert_eq!(catalogue.typical_duration("Kabaddi").num_minutes(), 50);
+/// assert_eq!(catalogue.typical_duration("Football").num_minutes(), 180);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catalogue(Vec<Sport>);
+
+impl Catalogue {
+    pub fn new(sports: Vec<Sport>) -> Catalogue {
+        Catalogue(sports)
+    }
+
+    /// The sports that we scrape
+    pub fn enabled(&self) -> Vec<Sport> {
+        self.0.iter().filter(|s| s.enabled).cloned().collect()
+    }
+
+    /// How long an event of `sport` usually lasts, [`DEFAULT_DURATION`] for sports we don't know
+    ///
+    /// # Arguments
+    /// * `sport` - The name of the sport, like "Football"
+    ///
+    /// # Example
+    /// ```
+    /// use scraper::constants::sports::Catalogue;
+    ///
+    /// let catalogue = Catalogue::default();
+    /// assert_eq!(catalogue.typical_duration("Football").num_minutes(), 120);
+    /// assert_eq!(catalogue.typical_duration("Quidditch").num_minutes(), 180);
+    /// ```
+    pub fn typical_duration(&self, sport: &str) -> chrono::Duration {
+        let minutes = self
+            .0
+            .iter()
+            .find(|s| s.name == sport)
+            .map_or(DEFAULT_DURATION, |s| s.duration);
+
+        chrono::Duration::minutes(minutes as i64)
+    }
+
+    /// The longest any event lasts, nothing that started longer ago than this can still be live
+    pub fn longest_duration(&self) -> chrono::Duration {
+        let minutes = self.0.iter().map(|s| s.duration).max().unwrap_or(DEFAULT_DURATION);
+
+        chrono::Duration::minutes(minutes as i64)
+    }
+}
+
+impl Default for Catalogue {
+    fn default() -> Self {
+        Catalogue(SPORTS.to_vec())
+    }
+}
+
+impl Deref for Catalogue {
+    type Target = [Sport];
+
+    fn deref(&self) -> &[Sport] {
+        &self.0
     }
 }
 
+/// A catalogue file in toml, the sports are `[[sports]]` tables
+#[derive(Deserialize)]
+struct CatalogueFile {
+    sports: Vec<Sport>,
+}
+
+/// Read a catalogue from a `.json` file (an array of sports) or a toml file (`[[sports]]` tables)
+///
+/// # Arguments
+/// * `path` - The catalogue file, `.json` is read as json and anything else as toml
+pub fn load_catalogue(path: &Path) -> Result<Vec<Sport>, Error> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("could not read the sports {}: {}", path.display(), e))?;
+
+    let is_json = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json"));
+
+    parse_catalogue(&contents, is_json).map_err(|e| anyhow::anyhow!("invalid sports {}: {}", path.display(), e))
+}
+
+/// Parse a catalogue, see [`load_catalogue`]
+///
+/// # Example
+/// ```
+/// use scraper::constants::sports::parse_catalogue;
+///
+/// let toml = r#"
+/// [[sports]]
+/// name = "Football"
+/// url = "https://reddit4.sportshub.stream/"
+/// duration = 120
+///
+/// [[sports]]
+/// name = "Darts"
+/// url = "https://darts1.sportshub.stream/"
+/// enabled = false
+/// "#;
+///
+/// let sports = parse_catalogue(toml, false).unwrap();
+/// assert_eq!(sports[0].url, "https://reddit4.sportshub.stream/");
+/// assert_eq!(sports[1].duration, 180);
+/// assert!(!sports[1].enabled);
+///
+/// let json = r#"[{"name": "Football", "url": "https://reddit4.sportshub.stream/"}]"#;
+/// assert_eq!(parse_catalogue(json, true).unwrap()[0].name, "Football");
+/// ```
+pub fn parse_catalogue(contents: &str, is_json: bool) -> Result<Vec<Sport>, Error> {
+    let sports: Vec<Sport> = if is_json {
+        serde_json::from_str(contents)?
+    } else {
+        toml::from_str::<CatalogueFile>(contents)?.sports
+    };
+
+    for (i, sport) in sports.iter().enumerate() {
+        if sports[..i].iter().any(|s| s.name == sport.name) {
+            anyhow::bail!("{} is in the catalogue twice", sport.name);
+        }
+    }
+
+    Ok(sports)
+}
+
 // used github copilot to generate this
-pub const SOCCER: Sport = Sport::new("Football", "https://reddit3.sportshub.stream/");
-pub const AMERICAN_FOOTBALL: Sport = Sport::new("AmericanFootball", "https://football.sportshub.stream/");
-pub const BASKETBALL: Sport = Sport::new("Basketball", "https://basketball2.sportshub.stream/");
-pub const BASEBALL: Sport = Sport::new("Baseball", "https://baseball.sportshub.stream/");
-pub const HOCKEY: Sport = Sport::new("Hockey", "https://hockey.sportshub.stream");
-pub const TENNIS: Sport = Sport::new("Tennis", "https://tennis.sportshub.stream/");
-pub const BOXING: Sport = Sport::new("Boxing", "https://boxing1.sportshub.stream/");
-pub const FIGHTS: Sport = Sport::new("Fights", "https://mma.sportshub.stream/");
+pub const SOCCER: Sport = Sport::new("Football", "https://reddit3.sportshub.stream/").lasting(120);
+pub const AMERICAN_FOOTBALL: Sport = Sport::new("AmericanFootball", "https://football.sportshub.stream/").lasting(210);
+pub const BASKETBALL: Sport = Sport::new("Basketball", "https://basketball2.sportshub.stream/").lasting(150);
+pub const BASEBALL: Sport = Sport::new("Baseball", "https://baseball.sportshub.stream/").lasting(210);
+pub const HOCKEY: Sport = Sport::new("Hockey", "https://hockey.sportshub.stream").lasting(165);
+pub const TENNIS: Sport = Sport::new("Tennis", "https://tennis.sportshub.stream/").lasting(240);
+pub const BOXING: Sport = Sport::new("Boxing", "https://boxing1.sportshub.stream/").lasting(240);
+pub const FIGHTS: Sport = Sport::new("Fights", "https://mma.sportshub.stream/").lasting(300);
 pub const MOTORSPORTS: Sport = Sport::new("Motorsports", "https://motorsport.sportshub.stream/");
-pub const HORSE_RACING: Sport = Sport::new("HorseRacing", "https://sportshub.stream/horse-racing-streams/");
-pub const RUGBY: Sport = Sport::new("Rugby", "https://rugby.sportshub.stream/");
-pub const RUGBY_UNION: Sport = Sport::new("RugbyUnion", "https://sportshub.stream/rugby-union-streams/");
-pub const RUGBY_SEVENS: Sport = Sport::new("RugbySevens", "https://sportshub.stream/rugby-sevens-streams/");
-pub const CYCLING: Sport = Sport::new("Cycling", "https://cycling.sportshub.stream/");
-pub const CRICKET: Sport = Sport::new("Cricket", "https://cricket.sportshub.stream/");
-pub const GOLF: Sport = Sport::new("Golf", "https://golf.sportshub.stream/");
+pub const HORSE_RACING: Sport = Sport::new("HorseRacing", "https://sportshub.stream/horse-racing-streams/").lasting(60);
+pub const RUGBY: Sport = Sport::new("Rugby", "https://rugby.sportshub.stream/").lasting(120);
+pub const RUGBY_UNION: Sport = Sport::new("RugbyUnion", "https://sportshub.stream/rugby-union-streams/").lasting(120);
+pub const RUGBY_SEVENS: Sport = Sport::new("RugbySevens", "https://sportshub.stream/rugby-sevens-streams/").lasting(60);
+pub const CYCLING: Sport = Sport::new("Cycling", "https://cycling.sportshub.stream/").lasting(360);
+pub const CRICKET: Sport = Sport::new("Cricket", "https://cricket.sportshub.stream/").lasting(480);
+pub const GOLF: Sport = Sport::new("Golf", "https://golf.sportshub.stream/").lasting(600);
 pub const AFL: Sport = Sport::new("AFL", "https://afl.sportshub.stream/");
-pub const VOLLEYBALL: Sport = Sport::new("Volleyball", "https://volleyball.sportshub.stream/");
-pub const SNOOKER: Sport = Sport::new("Snooker", "https://snooker.sportshub.stream/");
-pub const DARTS: Sport = Sport::new("Darts", "https://darts1.sportshub.stream/");
+pub const VOLLEYBALL: Sport = Sport::new("Volleyball", "https://volleyball.sportshub.stream/").lasting(150);
+pub const SNOOKER: Sport = Sport::new("Snooker", "https://snooker.sportshub.stream/").lasting(300);
+pub const DARTS: Sport = Sport::new("Darts", "https://darts1.sportshub.stream/").lasting(240);
 pub const WATERSPORTS: Sport = Sport::new("Watersports", "https://sportshub.stream/water-sports-streams/");
 pub const SUMMER_SPORTS: Sport = Sport::new("SummerSports", "https://sportshub.stream/summer-sports-streams/");
 pub const BEACH_SPORTS: Sport = Sport::new("BeachSports", "https://sportshub.stream/beach-soccer-streams/");
-pub const ESPORTS: Sport = Sport::new("Esports", "https://sportshub.stream/esports-streams/");
-pub const HANDBALL: Sport = Sport::new("Handball", "https://handball.sportshub.stream/");
-pub const ATHLETICS: Sport = Sport::new("Athletics", "https://sportshub.stream/athletics-streams/");
-pub const TRIATHLON: Sport = Sport::new("Triathlon", "https://sportshub.stream/thriatlon-streams/");
-pub const BEACH_VOLLEY: Sport = Sport::new("BeachVolley", "https://sportshub.stream/beach-volley-streams/");
-pub const WATER_POLO: Sport = Sport::new("WaterPolo", "https://sportshub.stream/water-polo-streams/");
-pub const BADMINTON: Sport = Sport::new("Badminton", "https://badminton.sportshub.stream/");
-pub const FLOORBALL: Sport = Sport::new("Floorball", "https://sportshub.stream/floorball-streams/");
-pub const FIELD_HOCKEY: Sport = Sport::new("FieldHockey", "https://sportshub.stream/field-hockey-streams/");
-pub const TABLE_TENNIS: Sport = Sport::new("TableTennis", "https://sportshub.stream/table-tennis-streams/");
+pub const ESPORTS: Sport = Sport::new("Esports", "https://sportshub.stream/esports-streams/").lasting(240);
+pub const HANDBALL: Sport = Sport::new("Handball", "https://handball.sportshub.stream/").lasting(90);
+pub const ATHLETICS: Sport = Sport::new("Athletics", "https://sportshub.stream/athletics-streams/").lasting(240);
+pub const TRIATHLON: Sport = Sport::new("Triathlon", "https://sportshub.stream/thriatlon-streams/").lasting(300);
+pub const BEACH_VOLLEY: Sport = Sport::new("BeachVolley", "https://sportshub.stream/beach-volley-streams/").lasting(90);
+pub const WATER_POLO: Sport = Sport::new("WaterPolo", "https://sportshub.stream/water-polo-streams/").lasting(90);
+pub const BADMINTON: Sport = Sport::new("Badminton", "https://badminton.sportshub.stream/").lasting(90);
+pub const FLOORBALL: Sport = Sport::new("Floorball", "https://sportshub.stream/floorball-streams/").lasting(120);
+pub const FIELD_HOCKEY: Sport = Sport::new("FieldHockey", "https://sportshub.stream/field-hockey-streams/").lasting(90);
+pub const TABLE_TENNIS: Sport = Sport::new("TableTennis", "https://sportshub.stream/table-tennis-streams/").lasting(90);
 pub const ROWING: Sport = Sport::new("Rowing", "https://sportshub.stream/rowing-streams/");
-pub const FUTSAL: Sport = Sport::new("Futsal", "https://sportshub.stream/futsal-streams/");
-pub const NETBALL: Sport = Sport::new("Netball", "https://sportshub.stream/netball-streams/");
+pub const FUTSAL: Sport = Sport::new("Futsal", "https://sportshub.stream/futsal-streams/").lasting(90);
+pub const NETBALL: Sport = Sport::new("Netball", "https://sportshub.stream/netball-streams/").lasting(90);
 pub const WINTER_SPORTS: Sport = Sport::new("WinterSports", "https://sportshub.stream/winter-sports-streams/");
 pub const CURLING: Sport = Sport::new("Curling", "https://sportshub.stream/curling-streams/");
 
@@ -1,8 +1,422 @@
+//! Parsing of the event times shown on the website, like "31st January at 19:30".
+//!
+//! The text is split into tokens (numbers, words and `:`) which are then read one by one,
+//! so the same parser handles "Today at 7:30 PM", "1 févr. à 19h30" or "31 января в 19:30".
+
 use anyhow::Error;
-use chrono::{Datelike, TimeZone, Utc};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, Offset, TimeZone, Utc};
+use thiserror::Error;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DateParseError {
+    #[error("empty date")]
+    Empty,
+    #[error("unknown word {0:?} in date")]
+    UnknownWord(String),
+    #[error("no time in date {0:?}")]
+    MissingTime(String),
+    #[error("no day in date {0:?}")]
+    MissingDay(String),
+    #[error("invalid date {0:?}")]
+    Invalid(String),
+}
+
+/// The day part of a date, as written on the page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Day {
+    /// the year is only there if the page shows it, otherwise we use the closest one
+    Date { day: u32, month: u32, year: Option<i32> },
+    Today,
+    Tomorrow,
+    Yesterday,
+}
+
+/// A date read from the page, in the timezone of the page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParsedDate {
+    pub day: Day,
+    pub hour: u32,
+    pub minute: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(u32),
+    Word(String),
+    Colon,
+}
+
+/// Month names and abbreviations in the languages the site is served in, without accents
+const MONTHS: &[(&str, u32)] = &[
+    // english
+    ("january", 1),
+    ("jan", 1),
+    ("february", 2),
+    ("feb", 2),
+    ("march", 3),
+    ("mar", 3),
+    ("april", 4),
+    ("apr", 4),
+    ("may", 5),
+    ("june", 6),
+    ("jun", 6),
+    ("july", 7),
+    ("jul", 7),
+    ("august", 8),
+    ("aug", 8),
+    ("september", 9),
+    ("sept", 9),
+    ("sep", 9),
+    ("october", 10),
+    ("oct", 10),
+    ("november", 11),
+    ("nov", 11),
+    ("december", 12),
+    ("dec", 12),
+    // spanish
+    ("enero", 1),
+    ("ene", 1),
+    ("febrero", 2),
+    ("marzo", 3),
+    ("abril", 4),
+    ("abr", 4),
+    ("mayo", 5),
+    ("junio", 6),
+    ("julio", 7),
+    ("agosto", 8),
+    ("ago", 8),
+    ("septiembre", 9),
+    ("setiembre", 9),
+    ("octubre", 10),
+    ("noviembre", 11),
+    ("diciembre", 12),
+    ("dic", 12),
+    // french
+    ("janvier", 1),
+    ("janv", 1),
+    ("fevrier", 2),
+    ("fevr", 2),
+    ("fev", 2),
+    ("mars", 3),
+    ("avril", 4),
+    ("avr", 4),
+    ("mai", 5),
+    ("juin", 6),
+    ("juillet", 7),
+    ("juil", 7),
+    ("aout", 8),
+    ("septembre", 9),
+    ("octobre", 10),
+    ("novembre", 11),
+    ("decembre", 12),
+    // german
+    ("januar", 1),
+    ("februar", 2),
+    ("marz", 3),
+    ("juni", 6),
+    ("juli", 7),
+    ("oktober", 10),
+    ("okt", 10),
+    ("dezember", 12),
+    ("dez", 12),
+    // italian
+    ("gennaio", 1),
+    ("gen", 1),
+    ("febbraio", 2),
+    ("aprile", 4),
+    ("maggio", 5),
+    ("mag", 5),
+    ("giugno", 6),
+    ("giu", 6),
+    ("luglio", 7),
+    ("lug", 7),
+    ("settembre", 9),
+    ("set", 9),
+    ("ottobre", 10),
+    ("ott", 10),
+    ("dicembre", 12),
+    // portuguese
+    ("janeiro", 1),
+    ("fevereiro", 2),
+    ("marco", 3),
+    ("maio", 5),
+    ("junho", 6),
+    ("julho", 7),
+    ("setembro", 9),
+    ("outubro", 10),
+    ("out", 10),
+    ("novembro", 11),
+    ("dezembro", 12),
+    // russian, the site uses the genitive form
+    ("января", 1),
+    ("февраля", 2),
+    ("марта", 3),
+    ("апреля", 4),
+    ("мая", 5),
+    ("маи", 5),
+    ("июня", 6),
+    ("июля", 7),
+    ("августа", 8),
+    ("сентября", 9),
+    ("октября", 10),
+    ("ноября", 11),
+    ("декабря", 12),
+];
+
+const TODAY: &[&str] = &["today", "hoy", "aujourd", "heute", "oggi", "hoje", "сегодня"];
+const TOMORROW: &[&str] = &["tomorrow", "manana", "demain", "morgen", "domani", "amanha", "завтра"];
+const YESTERDAY: &[&str] = &["yesterday", "ayer", "hier", "gestern", "ieri", "ontem", "вчера"];
+
+/// Ordinal suffixes, they come right after the day ("1st", "1er", "1º")
+const ORDINALS: &[&str] = &["st", "nd", "rd", "th", "er", "eme", "e", "º", "ª"];
+
+/// Words that don't change the date, like "at" or the name of the weekday
+const IGNORED: &[&str] = &[
+    "at", "on", "the", "of", "a", "o", "la", "las", "le", "les", "el", "de", "del", "do", "da", "um", "uhr", "alle",
+    "ore", "as", "hui", "h", "в", "г", "mon", "monday", "tue", "tuesday", "wed", "wednesday", "thu", "thursday", "fri",
+    "friday", "sat", "saturday", "sun", "sunday", "lunes", "martes", "miercoles", "jueves", "viernes", "sabado",
+    "domingo", "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche", "montag", "dienstag",
+    "mittwoch", "donnerstag", "freitag", "samstag", "sonntag", "lunedi", "martedi", "mercoledi", "giovedi", "venerdi",
+    "sabato", "domenica", "segunda", "terca", "quarta", "feira",
+];
+
+fn month_from_word(word: &str) -> Option<u32> {
+    MONTHS
+        .iter()
+        .find(|(name, _)| *name == word)
+        .map(|(_, month)| *month)
+}
+
+/// Lowercase the date and remove the accents, then split it into tokens
+fn tokenize(date: &str) -> Result<Vec<Token>, DateParseError> {
+    let text: String = date
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .replace("a.m.", "am")
+        .replace("p.m.", "pm");
+
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_ascii_digit() {
+            let mut number: u32 = 0;
+
+            while let Some(digit) = chars.peek().and_then(|d| d.to_digit(10)) {
+                number = number
+                    .checked_mul(10)
+                    .and_then(|n| n.checked_add(digit))
+                    .ok_or(DateParseError::Invalid(date.to_string()))?;
+                chars.next();
+            }
+
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() {
+            let mut word = String::new();
+
+            while let Some(&l) = chars.peek().filter(|l| l.is_alphabetic()) {
+                word.push(l);
+                chars.next();
+            }
+
+            tokens.push(Token::Word(word));
+        } else {
+            if c == ':' {
+                tokens.push(Token::Colon);
+            }
+
+            // anything else (spaces, commas, dots...) only separates the tokens
+            chars.next();
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Parse a date from the website, without turning it into a timestamp yet
+///
+/// # Arguments
+/// * `date` - The date string to parse
+///
+/// # Example
+/// ```
+/// use scraper::date_parser::{parse_date, Day, ParsedDate};
+///
+/// let parsed = parse_date("Tomorrow at 7:30 PM").unwrap();
+/// assert_eq!(parsed, ParsedDate { day: Day::Tomorrow, hour: 19, minute: 30 });
+///
+/// let parsed = parse_date("31st Jan, 19:30").unwrap();
+/// assert_eq!(parsed.day, Day::Date { day: 31, month: 1, year: None });
+///
+/// assert!(parse_date("whenever").is_err());
+/// ```
+pub fn parse_date(date: &str) -> Result<ParsedDate, DateParseError> {
+    let tokens = tokenize(date)?;
+
+    if tokens.is_empty() {
+        return Err(DateParseError::Empty);
+    }
+
+    let invalid = || DateParseError::Invalid(date.to_string());
+
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+    let mut relative = None;
+    let mut time = None;
+    let mut pm = None;
+
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            Token::Number(n) => {
+                let n = *n;
+
+                match (tokens.get(i + 1), tokens.get(i + 2)) {
+                    // 19:30 or 19h30
+                    (Some(Token::Colon), Some(Token::Number(m))) => {
+                        time = Some((n, *m));
+                        i += 3;
+                        continue;
+                    }
+                    (Some(Token::Word(h)), Some(Token::Number(m))) if h == "h" => {
+                        time = Some((n, *m));
+                        i += 3;
+                        continue;
+                    }
+                    // 7pm or 19h
+                    (Some(Token::Word(w)), _) if ["am", "pm", "h", "uhr"].contains(&w.as_str()) => {
+                        time = Some((n, 0));
+                    }
+                    _ if day.is_none() && relative.is_none() && (1..=31).contains(&n) => day = Some(n),
+                    _ if day.is_some() && month.is_none() && (1..=12).contains(&n) => month = Some(n),
+                    _ if year.is_none() && (1970..=9999).contains(&n) => year = Some(n as i32),
+                    _ => return Err(invalid()),
+                }
+            }
+            Token::Word(w) => {
+                let w = w.as_str();
+
+                if w == "am" || w == "pm" {
+                    pm = Some(w == "pm");
+                } else if let Some(m) = month_from_word(w) {
+                    if month.replace(m).is_some() {
+                        return Err(invalid());
+                    }
+                } else if TODAY.contains(&w) {
+                    relative = Some(Day::Today);
+                } else if TOMORROW.contains(&w) {
+                    relative = Some(Day::Tomorrow);
+                } else if YESTERDAY.contains(&w) {
+                    relative = Some(Day::Yesterday);
+                } else if !ORDINALS.contains(&w) && !IGNORED.contains(&w) {
+                    return Err(DateParseError::UnknownWord(w.to_string()));
+                }
+            }
+            Token::Colon => {}
+        }
+
+        i += 1;
+    }
+
+    let (mut hour, minute) = time.ok_or(DateParseError::MissingTime(date.to_string()))?;
+
+    match pm {
+        Some(_) if !(1..=12).contains(&hour) => return Err(invalid()),
+        Some(true) if hour < 12 => hour += 12,
+        Some(false) if hour == 12 => hour = 0,
+        _ => {}
+    }
+
+    if hour > 23 || minute > 59 {
+        return Err(invalid());
+    }
+
+    let day = match (relative, day, month) {
+        (Some(relative), None, None) => relative,
+        (None, Some(day), Some(month)) => Day::Date { day, month, year },
+        (Some(_), _, _) => return Err(invalid()),
+        (None, _, _) => return Err(DateParseError::MissingDay(date.to_string())),
+    };
+
+    Ok(ParsedDate { day, hour, minute })
+}
+
+impl ParsedDate {
+    /// The unix timestamp of the date, when the page shows times with the utc offset `offset`.
+    /// Dates without a year get the year that puts them closest to `now`,
+    /// "Today" and "Tomorrow" are relative to `now` too.
+    ///
+    /// # Arguments
+    /// * `offset` - The utc offset of the times on the page
+    /// * `now` - The reference time, usually when the scrape started
+    ///
+    /// # Example
+    /// ```
+    /// use chrono::{FixedOffset, TimeZone, Utc};
+    /// use scraper::date_parser::parse_date;
+    ///
+    /// let paris = FixedOffset::east_opt(3600).unwrap();
+    /// let now = Utc.with_ymd_and_hms(2024, 1, 20, 12, 0, 0).unwrap();
+    ///
+    /// let parsed = parse_date("1 February at 1:00").unwrap();
+    /// assert_eq!(parsed.to_timestamp(paris, now).unwrap(), 1_706_745_600);
+    ///
+    /// let parsed = parse_date("Tomorrow at 1:00").unwrap();
+    /// assert_eq!(parsed.to_timestamp(paris, now).unwrap(), 1_705_795_200);
+    /// ```
+    pub fn to_timestamp(&self, offset: FixedOffset, now: DateTime<Utc>) -> Result<i64, DateParseError> {
+        let today = now.with_timezone(&offset).date_naive();
+
+        let invalid = || DateParseError::Invalid(format!("{:?}", self));
+        let time = NaiveTime::from_hms_opt(self.hour, self.minute, 0).ok_or_else(invalid)?;
+
+        let timestamp_on = |date: NaiveDate| {
+            offset
+                .from_local_datetime(&date.and_time(time))
+                .single()
+                .map(|t| t.timestamp())
+        };
+
+        let timestamp = match self.day {
+            Day::Today => timestamp_on(today),
+            Day::Tomorrow => timestamp_on(today + Duration::days(1)),
+            Day::Yesterday => timestamp_on(today - Duration::days(1)),
+            Day::Date {
+                day,
+                month,
+                year: Some(year),
+            } => NaiveDate::from_ymd_opt(year, month, day).and_then(timestamp_on),
+            Day::Date { day, month, year: None } => closest_year(day, month, now, timestamp_on),
+        };
+
+        timestamp.ok_or_else(invalid)
+    }
+}
+
+/// The timestamp of `day`/`month` in the year that is closest to `now`
+fn closest_year(
+    day: u32,
+    month: u32,
+    now: DateTime<Utc>,
+    timestamp_on: impl Fn(NaiveDate) -> Option<i64>,
+) -> Option<i64> {
+    let now_timestamp = now.timestamp();
+
+    // the 29th of february doesn't exist every year, so some years can be missing
+    [now.year() - 1, now.year(), now.year() + 1]
+        .into_iter()
+        .filter_map(|year| NaiveDate::from_ymd_opt(year, month, day).and_then(&timestamp_on))
+        .min_by_key(|timestamp| (now_timestamp - timestamp).abs())
+}
 
 /// Parses a date string into a parsable format
-/// an example input is "1 February at 0:00"
+/// an example input is "1 February at 0:00".
+/// Returns an empty string for dates that can't be parsed or don't have a day and month,
+/// use [`parse_date`] to know why.
 ///
 /// # Arguments
 /// * `date` - The date string to parse
@@ -17,28 +431,14 @@ use chrono::{Datelike, TimeZone, Utc};
 /// assert_eq!(parsed_date, "1-2-0-00".to_string());
 /// ```
 pub fn date_parser(date: &str) -> String {
-    let date = date.trim();
-    let date = date.replace(" at ", " ");
-    let date = date.replace("January", "1");
-    let date = date.replace("February", "2");
-    let date = date.replace("March", "3");
-    let date = date.replace("April", "4");
-    let date = date.replace("May", "5");
-    let date = date.replace("June", "6");
-    let date = date.replace("July", "7");
-    let date = date.replace("August", "8");
-    let date = date.replace("September", "9");
-    let date = date.replace("October", "10");
-    let date = date.replace("November", "11");
-    let date = date.replace("December", "12");
-    let date = date.replace(":", " ");
-    let date = date.replace(" ", "-");
-    let date = date.replace("th", "");
-    let date = date.replace("st", "");
-    let date = date.replace("nd", "");
-    let date = date.replace("rd", "");
-
-    date
+    match parse_date(date) {
+        Ok(ParsedDate {
+            day: Day::Date { day, month, .. },
+            hour,
+            minute,
+        }) => format!("{}-{}-{}-{:02}", day, month, hour, minute),
+        _ => String::new(),
+    }
 }
 
 /// Parses a date string into a i64 timestamp
@@ -59,13 +459,20 @@ pub fn date_parser(date: &str) -> String {
 /// let parsed_date = date_parser_to_instant(date, year).unwrap();
 ///
 /// assert_eq!(parsed_date, 1_706_745_600);
+///
+/// assert!(date_parser_to_instant("1-2", year).is_err());
 /// ```
 pub fn date_parser_to_instant(date: &str, year: i32) -> Result<i64, Error> {
-    let date = date.split("-").collect::<Vec<&str>>();
-    let day = date[0].parse::<u32>().unwrap();
-    let month = date[1].parse::<u32>().unwrap();
-    let hour = date[2].parse::<u32>().unwrap();
-    let minute = date[3].parse::<u32>().unwrap();
+    let invalid = || DateParseError::Invalid(date.to_string());
+
+    let parts = date
+        .split('-')
+        .map(|part| part.parse::<u32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<u32>, DateParseError>>()?;
+
+    let [day, month, hour, minute] = parts[..] else {
+        return Err(invalid().into());
+    };
 
     let mut year = year;
     if day == 1 && month == 1 && hour == 1 && minute == 1 {
@@ -78,33 +485,36 @@ pub fn date_parser_to_instant(date: &str, year: i32) -> Result<i64, Error> {
         return Ok(time.timestamp());
     }
 
-    Err(anyhow::anyhow!("Could not parse date"))
+    Err(invalid().into())
 }
 
-/// Checks closest year to the current timestamp
+/// Checks closest year to the reference time
 /// and returns the timestamp of the date
 /// an example input is "1-2-0-00"
 ///
 /// # Arguments
 /// * `date` - The date string to parse
+/// * `now` - The reference time, usually when the scrape started
 ///
 /// # Example
 /// ```
+/// use chrono::{TimeZone, Utc};
 /// use scraper::date_parser::date_parser_with_closest_year;
 ///
+/// let now = Utc.with_ymd_and_hms(2024, 2, 10, 0, 0, 0).unwrap();
+///
 /// let date = "1-2-0-00";
-/// let parsed_date = date_parser_with_closest_year(date).unwrap();
+/// let parsed_date = date_parser_with_closest_year(date, now).unwrap();
 ///
 /// assert_eq!(parsed_date, 1_706_745_600);
-/// // This will be different depending on the current year
 ///
+/// // december is closer last year
 /// let date = "12-12-0-00";
-/// let parsed_date = date_parser_with_closest_year(date).unwrap();
+/// let parsed_date = date_parser_with_closest_year(date, now).unwrap();
 ///
 /// assert_eq!(parsed_date, 1_702_339_200);
 /// ```
-pub fn date_parser_with_closest_year(date: &str) -> anyhow::Result<i64> {
-    let now = Utc::now();
+pub fn date_parser_with_closest_year(date: &str, now: DateTime<Utc>) -> anyhow::Result<i64> {
     let year = now.year();
     let date_this_year = date_parser_to_instant(date, year)?;
     let date_next_year = date_parser_to_instant(date, year + 1)?;
@@ -125,31 +535,59 @@ pub fn date_parser_with_closest_year(date: &str) -> anyhow::Result<i64> {
     }
 }
 
-
 /// Parses a date string into a i32 seconds since epoch
 /// an example input is "1 February at 0:00"
 /// This function is used to parse the date string from the website
 /// into a timestamp that can be used to compare with the current time
-/// and check if the stream is live or not
+/// and check if the stream is live or not.
+/// The time is read as utc, use [`date_string_to_timestamp_in`] for pages in another timezone.
 /// # Arguments
 /// * `date` - The date string to parse
+/// * `now` - The reference time, usually when the scrape started
 ///
 /// # Example
 /// ```
+/// use chrono::{TimeZone, Utc};
 /// use scraper::date_parser::date_string_to_timestamp;
 ///
+/// let now = Utc.with_ymd_and_hms(2024, 2, 10, 0, 0, 0).unwrap();
+///
 /// let date = "1 February at 0:00";
-/// let parsed_date = date_string_to_timestamp(date).unwrap();
+/// let parsed_date = date_string_to_timestamp(date, now).unwrap();
 ///
 /// assert_eq!(parsed_date, 1_706_745_600);
 /// ```
-pub fn date_string_to_timestamp(date: &str) -> anyhow::Result<i64> {
-    let date = date_parser(date);
-    let date = date_parser_with_closest_year(&date)?;
-
-    Ok(date)
+pub fn date_string_to_timestamp(date: &str, now: DateTime<Utc>) -> anyhow::Result<i64> {
+    Ok(date_string_to_timestamp_in(date, Utc.fix(), now)?)
 }
 
+/// Parses a date string shown in the timezone `offset` into a unix timestamp
+///
+/// # Arguments
+/// * `date` - The date string to parse
+/// * `offset` - The utc offset of the times on the page
+/// * `now` - The reference time, usually when the scrape started
+///
+/// # Example
+/// ```
+/// use chrono::{FixedOffset, Utc};
+/// use scraper::date_parser::{date_string_to_timestamp_in, DateParseError};
+///
+/// let new_york = FixedOffset::west_opt(5 * 3600).unwrap();
+/// let parsed_date = date_string_to_timestamp_in("1 February 2024 at 7 PM", new_york, Utc::now()).unwrap();
+///
+/// assert_eq!(parsed_date, 1_706_832_000);
+///
+/// let error = date_string_to_timestamp_in("1 Brumaire at 7 PM", new_york, Utc::now()).unwrap_err();
+/// assert_eq!(error, DateParseError::UnknownWord("brumaire".to_string()));
+/// ```
+pub fn date_string_to_timestamp_in(
+    date: &str,
+    offset: FixedOffset,
+    now: DateTime<Utc>,
+) -> Result<i64, DateParseError> {
+    parse_date(date)?.to_timestamp(offset, now)
+}
 
 #[cfg(test)]
 mod test {
@@ -314,13 +752,20 @@ mod test {
         }
     }
 
+    /// the tests were written in february 2024, so the closest years are the ones around it
+    fn reference() -> chrono::DateTime<chrono::Utc> {
+        use chrono::TimeZone;
+
+        chrono::Utc.with_ymd_and_hms(2024, 2, 10, 0, 0, 0).unwrap()
+    }
+
     mod test_closest_year {
-        use super::super::date_parser_with_closest_year;
+        use super::{super::date_parser_with_closest_year, reference};
 
         #[test]
         fn test_1st_jan() {
             let date = "1-1-0-00";
-            let parsed_date = date_parser_with_closest_year(date).unwrap();
+            let parsed_date = date_parser_with_closest_year(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1704067200);
         }
@@ -328,7 +773,7 @@ mod test {
         #[test]
         fn test_2nd_feb() {
             let date = "2-2-0-00";
-            let parsed_date = date_parser_with_closest_year(date).unwrap();
+            let parsed_date = date_parser_with_closest_year(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1706832000);
         }
@@ -336,7 +781,7 @@ mod test {
         #[test]
         fn test_3rd_mar() {
             let date = "3-3-0-00";
-            let parsed_date = date_parser_with_closest_year(date).unwrap();
+            let parsed_date = date_parser_with_closest_year(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1709424000);
         }
@@ -344,7 +789,7 @@ mod test {
         #[test]
         fn test_4th_apr() {
             let date = "4-4-0-00";
-            let parsed_date = date_parser_with_closest_year(date).unwrap();
+            let parsed_date = date_parser_with_closest_year(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1712188800);
         }
@@ -352,7 +797,7 @@ mod test {
         #[test]
         fn test_random_date_1() {
             let date = "1-4-3-45";
-            let parsed_date = date_parser_with_closest_year(date).unwrap();
+            let parsed_date = date_parser_with_closest_year(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1711943100);
         }
@@ -360,7 +805,7 @@ mod test {
         #[test]
         fn test_random_date_2() {
             let date = "2-8-8-15";
-            let parsed_date = date_parser_with_closest_year(date).unwrap();
+            let parsed_date = date_parser_with_closest_year(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1722586500);
         }
@@ -368,7 +813,7 @@ mod test {
         #[test]
         fn test_random_date_3() {
             let date = "3-12-12-00";
-            let parsed_date = date_parser_with_closest_year(date).unwrap();
+            let parsed_date = date_parser_with_closest_year(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1701604800);
         }
@@ -376,7 +821,7 @@ mod test {
         #[test]
         fn test_random_date_4() {
             let date = "4-5-23-59";
-            let parsed_date = date_parser_with_closest_year(date).unwrap();
+            let parsed_date = date_parser_with_closest_year(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1714867140);
         }
@@ -384,17 +829,19 @@ mod test {
         #[test]
         fn test_random_date_5() {
             let date = "5-6-23-59";
-            let parsed_date = date_parser_with_closest_year(date).unwrap();
+            let parsed_date = date_parser_with_closest_year(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1717631940);
         }
     }
 
     mod test_combined_parsing {
+        use super::reference;
+
         #[test]
         fn test_1st_jan() {
             let date = "1st January at 0:00";
-            let parsed_date = super::super::date_string_to_timestamp(date).unwrap();
+            let parsed_date = super::super::date_string_to_timestamp(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1704067200);
         }
@@ -402,7 +849,7 @@ mod test {
         #[test]
         fn test_2nd_feb() {
             let date = "2nd February at 0:00";
-            let parsed_date = super::super::date_string_to_timestamp(date).unwrap();
+            let parsed_date = super::super::date_string_to_timestamp(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1706832000);
         }
@@ -410,7 +857,7 @@ mod test {
         #[test]
         fn test_3rd_mar() {
             let date = "3rd March at 0:00";
-            let parsed_date = super::super::date_string_to_timestamp(date).unwrap();
+            let parsed_date = super::super::date_string_to_timestamp(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1709424000);
         }
@@ -418,7 +865,7 @@ mod test {
         #[test]
         fn test_4th_apr() {
             let date = "4th April at 0:00";
-            let parsed_date = super::super::date_string_to_timestamp(date).unwrap();
+            let parsed_date = super::super::date_string_to_timestamp(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1712188800);
         }
@@ -426,7 +873,7 @@ mod test {
         #[test]
         fn test_random_date_1() {
             let date = "1st April at 3:45";
-            let parsed_date = super::super::date_string_to_timestamp(date).unwrap();
+            let parsed_date = super::super::date_string_to_timestamp(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1711943100);
         }
@@ -434,7 +881,7 @@ mod test {
         #[test]
         fn test_random_date_2() {
             let date = "2nd August at 8:15";
-            let parsed_date = super::super::date_string_to_timestamp(date).unwrap();
+            let parsed_date = super::super::date_string_to_timestamp(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1722586500);
         }
@@ -442,7 +889,7 @@ mod test {
         #[test]
         fn test_random_date_3() {
             let date = "3rd December at 12:00";
-            let parsed_date = super::super::date_string_to_timestamp(date).unwrap();
+            let parsed_date = super::super::date_string_to_timestamp(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1701604800);
         }
@@ -450,7 +897,7 @@ mod test {
         #[test]
         fn test_random_date_4() {
             let date = "4th May at 23:59";
-            let parsed_date = super::super::date_string_to_timestamp(date).unwrap();
+            let parsed_date = super::super::date_string_to_timestamp(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1714867140);
         }
@@ -458,9 +905,132 @@ mod test {
         #[test]
         fn test_random_date_5() {
             let date = "5th June at 23:59";
-            let parsed_date = super::super::date_string_to_timestamp(date).unwrap();
+            let parsed_date = super::super::date_string_to_timestamp(date, reference()).unwrap();
 
             assert_eq!(parsed_date, 1717631940);
         }
     }
+
+    mod test_parse_date {
+        use super::super::{parse_date, DateParseError, Day, ParsedDate};
+
+        fn date(day: u32, month: u32, hour: u32, minute: u32) -> ParsedDate {
+            ParsedDate {
+                day: Day::Date { day, month, year: None },
+                hour,
+                minute,
+            }
+        }
+
+        #[test]
+        fn test_relative_days() {
+            let today = parse_date("Today at 19:30").unwrap();
+            assert_eq!(today, ParsedDate { day: Day::Today, hour: 19, minute: 30 });
+
+            let tomorrow = parse_date("Mañana a las 21:00").unwrap();
+            assert_eq!(tomorrow.day, Day::Tomorrow);
+        }
+
+        #[test]
+        fn test_12h_times() {
+            assert_eq!(parse_date("1st February at 7:30 PM").unwrap(), date(1, 2, 19, 30));
+            assert_eq!(parse_date("1st February at 12am").unwrap(), date(1, 2, 0, 0));
+            assert_eq!(parse_date("1st February at 12:15 p.m.").unwrap(), date(1, 2, 12, 15));
+        }
+
+        #[test]
+        fn test_abbreviated_months() {
+            assert_eq!(parse_date("Sat, 3 Aug 19:30").unwrap(), date(3, 8, 19, 30));
+            assert_eq!(parse_date("3 Sept at 8:00").unwrap(), date(3, 9, 8, 0));
+        }
+
+        #[test]
+        fn test_other_languages() {
+            assert_eq!(parse_date("1er février à 19h30").unwrap(), date(1, 2, 19, 30));
+            assert_eq!(parse_date("31 января в 19:30").unwrap(), date(31, 1, 19, 30));
+            assert_eq!(parse_date("3. März um 20:45 Uhr").unwrap(), date(3, 3, 20, 45));
+            assert_eq!(parse_date("4 de mayo a las 23:59").unwrap(), date(4, 5, 23, 59));
+        }
+
+        #[test]
+        fn test_ordinal_suffixes_dont_mangle_words() {
+            // "August" and "Thursday" contain "st" and "th"
+            assert_eq!(parse_date("Thursday 1st August at 20:00").unwrap(), date(1, 8, 20, 0));
+        }
+
+        #[test]
+        fn test_errors() {
+            assert_eq!(parse_date(""), Err(DateParseError::Empty));
+            assert_eq!(parse_date("1 February"), Err(DateParseError::MissingTime("1 February".to_string())));
+            assert_eq!(parse_date("at 19:30"), Err(DateParseError::MissingDay("at 19:30".to_string())));
+            assert_eq!(parse_date("Soon"), Err(DateParseError::UnknownWord("soon".to_string())));
+            assert!(matches!(parse_date("1 February at 25:00"), Err(DateParseError::Invalid(_))));
+            assert!(matches!(parse_date("1 February at 13 PM"), Err(DateParseError::Invalid(_))));
+        }
+    }
+
+    mod test_timezone {
+        use chrono::FixedOffset;
+
+        use super::{super::date_string_to_timestamp_in, reference};
+
+        #[test]
+        fn test_offset_is_applied() {
+            let utc =
+                date_string_to_timestamp_in("1 February at 19:30", FixedOffset::east_opt(0).unwrap(), reference());
+            let kyiv =
+                date_string_to_timestamp_in("1 February at 21:30", FixedOffset::east_opt(7200).unwrap(), reference());
+
+            assert_eq!(utc, kyiv);
+            assert_eq!(utc.unwrap(), 1706815800);
+        }
+    }
+
+    mod test_year_rollover {
+        use chrono::{FixedOffset, TimeZone, Utc};
+
+        use super::super::{date_parser_with_closest_year, date_string_to_timestamp, date_string_to_timestamp_in};
+
+        #[test]
+        fn test_january_seen_in_december() {
+            let now = Utc.with_ymd_and_hms(2024, 12, 31, 23, 0, 0).unwrap();
+            let parsed_date = date_string_to_timestamp("1st January at 1:00", now).unwrap();
+
+            assert_eq!(parsed_date, Utc.with_ymd_and_hms(2025, 1, 1, 1, 0, 0).unwrap().timestamp());
+        }
+
+        #[test]
+        fn test_december_seen_in_january() {
+            let now = Utc.with_ymd_and_hms(2025, 1, 1, 0, 30, 0).unwrap();
+            let parsed_date = date_string_to_timestamp("31st December at 23:00", now).unwrap();
+
+            assert_eq!(parsed_date, Utc.with_ymd_and_hms(2024, 12, 31, 23, 0, 0).unwrap().timestamp());
+        }
+
+        #[test]
+        fn test_closest_year_in_december() {
+            let now = Utc.with_ymd_and_hms(2023, 12, 30, 12, 0, 0).unwrap();
+            let parsed_date = date_parser_with_closest_year("2-1-20-00", now).unwrap();
+
+            assert_eq!(parsed_date, Utc.with_ymd_and_hms(2024, 1, 2, 20, 0, 0).unwrap().timestamp());
+        }
+
+        #[test]
+        fn test_tomorrow_on_new_years_eve() {
+            let now = Utc.with_ymd_and_hms(2024, 12, 31, 20, 0, 0).unwrap();
+            let parsed_date = date_string_to_timestamp("Tomorrow at 15:00", now).unwrap();
+
+            assert_eq!(parsed_date, Utc.with_ymd_and_hms(2025, 1, 1, 15, 0, 0).unwrap().timestamp());
+        }
+
+        #[test]
+        fn test_rollover_uses_the_page_timezone() {
+            // it is already 2025 in Tokyo, so "Today" is the 1st of January there
+            let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+            let now = Utc.with_ymd_and_hms(2024, 12, 31, 20, 0, 0).unwrap();
+            let parsed_date = date_string_to_timestamp_in("Today at 18:00", tokyo, now).unwrap();
+
+            assert_eq!(parsed_date, Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap().timestamp());
+        }
+    }
 }
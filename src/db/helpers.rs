@@ -1,77 +1,660 @@
 //! Database operation helpers for sqlite, using diesel
 
-use std::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
 
 use anyhow::Error;
-use diesel::{prelude::*, sqlite::Sqlite, RunQueryDsl};
+use diesel::{
+    connection::SimpleConnection,
+    migration::Migration,
+    prelude::*,
+    r2d2::{ConnectionManager, CustomizeConnection, Pool},
+    sqlite::Sqlite,
+    RunQueryDsl,
+};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use serde::{Deserialize, Serialize};
 
 use super::{
-    models::{Stream, StreamNew},
+    models::{
+        EventLinkHistory,
+        EventStatus,
+        Link,
+        LinkCheck,
+        LinkHistory,
+        LinkInfo,
+        LinkNew,
+        LinkSighting,
+        LinkSightingNew,
+        MirrorHealth,
+        ScrapeReport,
+        ScrapeRun,
+        Stream,
+        StreamNew,
+        StreamWithLinks,
+        Team,
+        TeamNew,
+    },
     schema,
-    schema::stream::{self, dsl::*},
+    schema::{
+        link_sightings,
+        links,
+        mirror_health,
+        scrape_meta,
+        scrape_runs,
+        stream::{self, dsl::*},
+        team_aliases,
+        teams,
+    },
 };
+use crate::{config::MEMORY_DATABASE, constants::sports::Catalogue, search, teams::TeamAliases};
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
-pub fn run_migrations(
-    connection: &mut impl MigrationHarness<Sqlite>,
-    conn: &mut SqliteConnection,
-) -> Result<(), Error> {
-    connection.run_pending_migrations(MIGRATIONS).unwrap();
+/// Run the migrations that haven't been applied yet, nothing else is changed
+///
+/// # Returns
+/// The versions of the migrations that were applied, empty if the database was up to date
+pub fn run_migrations(conn: &mut impl MigrationHarness<Sqlite>) -> Result<Vec<String>, Error> {
+    let applied = conn
+        .run_pending_migrations(MIGRATIONS)
+        .map_err(|e| anyhow::anyhow!("could not run the migrations: {}", e))?;
 
-    delete_all_past_streams(conn)?;
+    Ok(applied.iter().map(|v| v.to_string()).collect())
+}
 
-    Ok(())
+/// The names of the migrations that haven't been applied yet
+pub fn pending_migrations(conn: &mut impl MigrationHarness<Sqlite>) -> Result<Vec<String>, Error> {
+    let pending = conn
+        .pending_migrations(MIGRATIONS)
+        .map_err(|e| anyhow::anyhow!("could not read the pending migrations: {}", e))?;
+
+    Ok(pending.iter().map(|m| m.name().to_string()).collect())
 }
 
-pub fn establish_connection() -> Result<SqliteConnection, anyhow::Error> {
-    let database_url = format!("{}/sports.db", std::env::temp_dir().display());
+/// The versions of the migrations already applied, oldest first
+pub fn applied_migrations(conn: &mut impl MigrationHarness<Sqlite>) -> Result<Vec<String>, Error> {
+    let mut applied = conn
+        .applied_migrations()
+        .map_err(|e| anyhow::anyhow!("could not read the applied migrations: {}", e))?;
+    applied.sort();
 
-    Ok(SqliteConnection::establish(&database_url)?)
+    Ok(applied.iter().map(|v| v.to_string()).collect())
 }
 
-pub fn establish_test_connection() -> Result<SqliteConnection, anyhow::Error> {
-    let database_url = format!("{}/sports.db", std::env::temp_dir().display());
+/// The file of the database at `database_url`, which is a path or a `file:` uri like sqlite takes it
+/// (diesel turns `sqlite://` into `file:`), without the query parameters.
+///
+/// # Example
+/// ```
+/// use std::path::PathBuf;
+///
+/// use scraper::db::helpers::database_path;
+///
+/// assert_eq!(database_path("sports.db"), PathBuf::from("sports.db"));
+/// assert_eq!(database_path("file:sports.db?mode=ro"), PathBuf::from("sports.db"));
+/// assert_eq!(database_path("sqlite:///var/lib/my%20sports.db"), PathBuf::from("/var/lib/my sports.db"));
+/// assert_eq!(database_path("file://localhost/var/lib/sports.db"), PathBuf::from("/var/lib/sports.db"));
+/// ```
+pub fn database_path(database_url: &str) -> PathBuf {
+    let Some(uri) = database_url
+        .strip_prefix("sqlite://")
+        .or_else(|| database_url.strip_prefix("file:"))
+    else {
+        return PathBuf::from(database_url);
+    };
+
+    let path = uri.split(['?', '#']).next().unwrap_or_default();
 
-    let mut conn = SqliteConnection::establish(&database_url)?;
-    run_migrations(&mut conn, &mut SqliteConnection::establish(&database_url)?)?;
+    // an authority can only be empty or localhost, the path starts right after it
+    let path = path
+        .strip_prefix("//localhost")
+        .or_else(|| path.strip_prefix("//"))
+        .unwrap_or(path);
+
+    PathBuf::from(urlencoding::decode(path).map_or(path.into(), |p| p.into_owned()))
+}
+
+/// Opens the database at `database_url`, see [`crate::config::Config::database_url`].
+/// An in-memory database (`:memory:`) starts empty, so the migrations are run on it straight away.
+pub fn establish_connection(database_url: &str) -> Result<SqliteConnection, anyhow::Error> {
+    let mut conn = SqliteConnection::establish(database_url)?;
+
+    if database_url == MEMORY_DATABASE {
+        conn.run_pending_migrations(MIGRATIONS)
+            .map_err(|e| anyhow::anyhow!(e))?;
+    }
 
     Ok(conn)
 }
 
+/// A pool of sqlite connections, used by the web server
+pub type DbPool = Pool<ConnectionManager<SqliteConnection>>;
+
+/// Settings applied to every pooled connection, so concurrent requests wait
+/// for a lock instead of failing straight away
+#[derive(Debug)]
+struct ConnectionOptions {
+    memory: bool,
+}
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for ConnectionOptions {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        conn.batch_execute("PRAGMA busy_timeout = 5000;")
+            .map_err(diesel::r2d2::Error::QueryError)?;
+
+        if !self.memory {
+            conn.batch_execute("PRAGMA journal_mode = WAL;")
+                .map_err(diesel::r2d2::Error::QueryError)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Creates a connection pool for the database at `database_url`.
+/// An in-memory database only exists as long as its connection, so the pool keeps
+/// a single connection open forever and runs the migrations on it.
+pub fn create_pool(database_url: &str) -> Result<DbPool, anyhow::Error> {
+    let memory = database_url == MEMORY_DATABASE;
+
+    let builder = Pool::builder().connection_customizer(Box::new(ConnectionOptions { memory }));
+
+    let pool = if memory {
+        builder
+            .max_size(1)
+            .max_lifetime(None)
+            .idle_timeout(None)
+            .build(ConnectionManager::new(database_url))?
+    } else {
+        builder.build(ConnectionManager::new(database_url))?
+    };
+
+    if memory {
+        pool.get()?
+            .run_pending_migrations(MIGRATIONS)
+            .map_err(|e| anyhow::anyhow!(e))?;
+    }
+
+    Ok(pool)
+}
+
+/// A fresh in-memory database with all migrations run, every test gets its own
+pub fn establish_test_connection() -> Result<SqliteConnection, anyhow::Error> {
+    establish_connection(MEMORY_DATABASE)
+}
+
 pub fn create_stream(conn: &mut SqliteConnection, new_stream: &StreamNew) -> Result<usize, anyhow::Error> {
     Ok(diesel::insert_or_ignore_into(stream::table)
         .values(new_stream)
         .execute(conn)?)
 }
 
+/// The links still on the page that aren't known to be dead,
+/// the removed ones are only kept for their history
+fn current_links() -> links::BoxedQuery<'static, Sqlite> {
+    links::table
+        .filter(links::removed_at.is_null())
+        .filter(links::reachable.is_null().or(links::reachable.eq(true)))
+        .into_boxed()
+}
+
+/// The events that are not archived, every getter below starts from this
+fn current_streams<'a>() -> stream::BoxedQuery<'a, Sqlite> {
+    stream.filter(archived_at.is_null()).into_boxed()
+}
+
 pub fn get_streams(conn: &mut SqliteConnection) -> Result<Vec<Stream>, anyhow::Error> {
-    Ok(stream.load::<Stream>(conn)?)
+    Ok(current_streams().load::<Stream>(conn)?)
 }
 
 pub fn get_empty_streams(conn: &mut SqliteConnection) -> Result<Vec<Stream>, anyhow::Error> {
-    Ok(stream.filter(schema::stream::stream_link.eq("")).load::<Stream>(conn)?)
+    Ok(current_streams()
+        .filter(diesel::dsl::not(
+            schema::stream::id.eq_any(current_links().select(links::event_id.nullable())),
+        ))
+        .load::<Stream>(conn)?)
+}
+
+/// The events whose pages we scrape the links of: the ones without links yet, and the upcoming and live ones
+/// that have some, so the links that are added or pulled while the event matters are tracked too
+///
+/// # Arguments
+/// * `catalogue` - The sports, for how long the events last
+/// * `now` - The current time
+pub fn get_streams_to_update(
+    conn: &mut SqliteConnection,
+    catalogue: &Catalogue,
+    now: chrono::NaiveDateTime,
+) -> Result<Vec<Stream>, anyhow::Error> {
+    let linked: HashSet<i32> = current_links()
+        .select(links::event_id)
+        .load::<i32>(conn)?
+        .into_iter()
+        .collect();
+
+    Ok(current_streams()
+        .load::<Stream>(conn)?
+        .into_iter()
+        .filter(|s| {
+            let has_links = s.id.is_some_and(|i| linked.contains(&i));
+            !has_links || s.status(catalogue, now) != EventStatus::Finished
+        })
+        .collect())
 }
 
 pub fn get_linked_streams(conn: &mut SqliteConnection) -> Result<Vec<Stream>, anyhow::Error> {
-    Ok(stream.filter(schema::stream::stream_link.ne("")).load::<Stream>(conn)?)
+    Ok(current_streams()
+        .filter(schema::stream::id.eq_any(current_links().select(links::event_id.nullable())))
+        .load::<Stream>(conn)?)
+}
+
+pub fn get_streams_by_url(conn: &mut SqliteConnection, search_url: &str) -> Result<Vec<Stream>, anyhow::Error> {
+    Ok(current_streams()
+        .filter(schema::stream::url.eq(search_url))
+        .load::<Stream>(conn)?)
+}
+
+/// Saves the stream links found on the page of an event, in the order they appear.
+/// Links we already know get their `last_seen` and metadata updated, links that are no longer
+/// on the page are marked as removed and kept for their history, see [`get_link_history`].
+pub fn save_links(
+    conn: &mut SqliteConnection,
+    search_event_id: i32,
+    found: &[LinkInfo],
+    now: chrono::NaiveDateTime,
+) -> Result<usize, anyhow::Error> {
+    let urls: Vec<&str> = found.iter().map(|l| l.url.as_str()).collect();
+
+    conn.transaction(|conn| {
+        let known: HashMap<String, Link> = links::table
+            .filter(links::event_id.eq(search_event_id))
+            .load::<Link>(conn)?
+            .into_iter()
+            .map(|l| (l.url.clone(), l))
+            .collect();
+
+        for (i, info) in found.iter().enumerate() {
+            let new_link = LinkNew {
+                event_id: search_event_id,
+                url: &info.url,
+                position: i as i32,
+                first_seen: now,
+                last_seen: now,
+                streamer: info.streamer.as_deref(),
+                language: info.language.as_deref(),
+                quality: info.quality.as_deref(),
+                ad_count: info.ad_count,
+                channel_type: info.channel_type.as_deref(),
+            };
+
+            diesel::insert_into(links::table)
+                .values(&new_link)
+                .on_conflict((links::event_id, links::url))
+                .do_update()
+                .set((
+                    links::position.eq(new_link.position),
+                    links::last_seen.eq(now),
+                    links::streamer.eq(new_link.streamer),
+                    links::language.eq(new_link.language),
+                    links::quality.eq(new_link.quality),
+                    links::ad_count.eq(new_link.ad_count),
+                    links::channel_type.eq(new_link.channel_type),
+                    links::removed_at.eq(None::<chrono::NaiveDateTime>),
+                ))
+                .execute(conn)?;
+
+            let link_id = links::table
+                .filter(links::event_id.eq(search_event_id))
+                .filter(links::url.eq(&info.url))
+                .select(links::id.assume_not_null())
+                .get_result::<i32>(conn)?;
+
+            match known.get(&info.url) {
+                // still on the page, the current sighting goes on
+                Some(link) if link.removed_at.is_none() => {
+                    diesel::update(
+                        link_sightings::table
+                            .filter(link_sightings::link_id.eq(link_id))
+                            .filter(link_sightings::removed_at.is_null()),
+                    )
+                    .set(link_sightings::last_seen.eq(now))
+                    .execute(conn)?;
+                }
+                // a new link, or one that was pulled and is back
+                _ => {
+                    diesel::insert_into(link_sightings::table)
+                        .values(&LinkSightingNew {
+                            link_id,
+                            first_seen: now,
+                            last_seen: now,
+                        })
+                        .execute(conn)?;
+                }
+            }
+        }
+
+        let gone = links::table
+            .filter(links::event_id.eq(search_event_id))
+            .filter(links::removed_at.is_null())
+            .filter(diesel::dsl::not(links::url.eq_any(&urls)));
+
+        diesel::update(
+            link_sightings::table
+                .filter(link_sightings::link_id.nullable().eq_any(gone.clone().select(links::id)))
+                .filter(link_sightings::removed_at.is_null()),
+        )
+        .set(link_sightings::removed_at.eq(now))
+        .execute(conn)?;
+
+        diesel::update(gone).set(links::removed_at.eq(now)).execute(conn)?;
+
+        Ok(found.len())
+    })
+}
+
+/// The links currently on the page of an event, in the order they appear
+pub fn get_links(conn: &mut SqliteConnection, search_event_id: i32) -> Result<Vec<Link>, anyhow::Error> {
+    Ok(links::table
+        .filter(links::event_id.eq(search_event_id))
+        .filter(links::removed_at.is_null())
+        .order(links::position)
+        .load::<Link>(conn)?)
+}
+
+/// How many events there are, without the archived ones
+pub fn count_streams(conn: &mut SqliteConnection) -> Result<i64, anyhow::Error> {
+    Ok(current_streams().count().get_result(conn)?)
+}
+
+/// How many links are currently on the pages of the events
+pub fn count_links(conn: &mut SqliteConnection) -> Result<i64, anyhow::Error> {
+    Ok(links::table
+        .filter(links::removed_at.is_null())
+        .count()
+        .get_result(conn)?)
+}
+
+/// Every link that was ever on the page of an event, with when it was there and how often it was pulled.
+/// Returns `None` if there is no such event.
+pub fn get_link_history(
+    conn: &mut SqliteConnection,
+    catalogue: &Catalogue,
+    search_event_id: i32,
+) -> Result<Option<EventLinkHistory>, anyhow::Error> {
+    let Some(event) = get_streams_by_id(conn, search_event_id)?.into_iter().next() else {
+        return Ok(None);
+    };
+
+    let event_links = links::table
+        .filter(links::event_id.eq(search_event_id))
+        .order((links::first_seen, links::position))
+        .load::<Link>(conn)?;
+
+    let ids: Vec<i32> = event_links.iter().filter_map(|l| l.id).collect();
+    let mut by_link: HashMap<i32, Vec<LinkSighting>> = HashMap::new();
+
+    for sighting in link_sightings::table
+        .filter(link_sightings::link_id.eq_any(ids))
+        .order((link_sightings::link_id, link_sightings::first_seen))
+        .load::<LinkSighting>(conn)?
+    {
+        by_link.entry(sighting.link_id).or_default().push(sighting);
+    }
+
+    let ends = event.start_time + catalogue.typical_duration(&event.sport);
+    let is_live = |t: chrono::NaiveDateTime| t >= event.start_time && t < ends;
+
+    let history: Vec<LinkHistory> = event_links
+        .into_iter()
+        .map(|link| {
+            let sightings = link.id.and_then(|i| by_link.remove(&i)).unwrap_or_default();
+
+            LinkHistory {
+                stable: sightings.len() <= 1 && link.removed_at.is_none(),
+                pulled_mid_game: sightings.iter().filter_map(|s| s.removed_at).any(is_live),
+                link,
+                sightings,
+            }
+        })
+        .collect();
+
+    let sightings = || history.iter().flat_map(|h| h.sightings.iter());
+
+    Ok(Some(EventLinkHistory {
+        event_id: search_event_id,
+        added: history.len(),
+        removed: sightings().filter(|s| s.removed_at.is_some()).count(),
+        returned: sightings().count().saturating_sub(history.len()),
+        links: history,
+    }))
+}
+
+/// The links to check, the ones on the page of a current event that were never checked or not since `checked_before`
+pub fn get_links_to_check(
+    conn: &mut SqliteConnection,
+    checked_before: chrono::NaiveDateTime,
+) -> Result<Vec<Link>, anyhow::Error> {
+    Ok(links::table
+        .filter(links::removed_at.is_null())
+        .filter(links::checked_at.is_null().or(links::checked_at.lt(checked_before)))
+        .filter(links::event_id.nullable().eq_any(current_streams().select(schema::stream::id)))
+        .order((links::event_id, links::position))
+        .load::<Link>(conn)?)
+}
+
+/// Save the result of checking a link
+///
+/// # Arguments
+/// * `link_id` - The id of the link in the `links` table
+/// * `check` - What we found when opening it
+/// * `now` - When it was checked
+pub fn save_link_check(
+    conn: &mut SqliteConnection,
+    link_id: i32,
+    check: &LinkCheck,
+    now: chrono::NaiveDateTime,
+) -> Result<(), anyhow::Error> {
+    diesel::update(links::table.filter(links::id.eq(link_id)))
+        .set((check, links::checked_at.eq(now)))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Loads the links of every stream with a single query and pairs them up,
+/// links that were checked and found dead are left out. The status of the events is the one they have now.
+pub fn attach_links(
+    conn: &mut SqliteConnection,
+    catalogue: &Catalogue,
+    streams: Vec<Stream>,
+) -> Result<Vec<StreamWithLinks>, anyhow::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let ids: Vec<i32> = streams.iter().filter_map(|s| s.id).collect();
+
+    let mut by_event: HashMap<i32, Vec<Link>> = HashMap::new();
+
+    for link in current_links()
+        .filter(links::event_id.eq_any(ids))
+        .order((links::event_id, links::position))
+        .load::<Link>(conn)?
+    {
+        by_event.entry(link.event_id).or_default().push(link);
+    }
+
+    Ok(streams
+        .into_iter()
+        .map(|s| {
+            let event_links = s.id.and_then(|i| by_event.remove(&i)).unwrap_or_default();
+
+            StreamWithLinks {
+                status: s.status(catalogue, now),
+                stream: s,
+                stream_link: event_links.iter().map(|l| l.url.clone()).collect(),
+                links: event_links,
+            }
+        })
+        .collect())
 }
 
 pub fn get_streams_by_id(conn: &mut SqliteConnection, search_id: i32) -> Result<Vec<Stream>, anyhow::Error> {
     Ok(stream.filter(schema::stream::id.eq(search_id)).load::<Stream>(conn)?)
 }
 
-pub fn delete_all_past_streams(conn: &mut SqliteConnection) -> Result<usize, anyhow::Error> {
-    Ok(
-        diesel::delete(stream.filter(start_time.le(chrono::Utc::now().naive_utc() - Duration::from_secs(3 * 60 * 60))))
-            .execute(conn)?,
+/// How long we keep an event after it most likely finished, in minutes
+pub const FINISHED_GRACE_MINUTES: i64 = 60;
+
+/// Archive the events that finished more than [`FINISHED_GRACE_MINUTES`] before `now`, their links are kept.
+/// When an event finishes depends on its sport, a golf tournament lasts longer than a football game.
+///
+/// # Arguments
+/// * `catalogue` - The sports, for how long the events last
+/// * `now` - The current time, it is also saved as the time the events were archived
+pub fn archive_past_streams(
+    conn: &mut SqliteConnection,
+    catalogue: &Catalogue,
+    now: chrono::NaiveDateTime,
+) -> Result<usize, anyhow::Error> {
+    let grace = chrono::Duration::minutes(FINISHED_GRACE_MINUTES);
+
+    let past: Vec<i32> = current_streams()
+        .filter(start_time.le(now - grace))
+        .load::<Stream>(conn)?
+        .into_iter()
+        .filter(|s| s.start_time + catalogue.typical_duration(&s.sport) + grace <= now)
+        .filter_map(|s| s.id)
+        .collect();
+
+    Ok(diesel::update(stream.filter(schema::stream::id.assume_not_null().eq_any(&past)))
+        .set(archived_at.eq(now))
+        .execute(conn)?)
+}
+
+/// Archive every current event, whether it finished or not
+pub fn archive_all_streams(conn: &mut SqliteConnection, now: chrono::NaiveDateTime) -> Result<usize, anyhow::Error> {
+    Ok(diesel::update(stream.filter(archived_at.is_null()))
+        .set(archived_at.eq(now))
+        .execute(conn)?)
+}
+
+/// Delete the events archived before `before` for good, with their links
+///
+/// # Arguments
+/// * `before` - Usually now minus the retention period, see [`crate::config::Config::retention`]
+pub fn purge_archived_streams(
+    conn: &mut SqliteConnection,
+    before: chrono::NaiveDateTime,
+) -> Result<usize, anyhow::Error> {
+    let expired = stream.filter(archived_at.lt(before));
+    let expired_links = links::table.filter(links::event_id.nullable().eq_any(expired.select(schema::stream::id)));
+
+    diesel::delete(
+        link_sightings::table.filter(link_sightings::link_id.nullable().eq_any(expired_links.select(links::id))),
     )
+    .execute(conn)?;
+    diesel::delete(expired_links).execute(conn)?;
+
+    Ok(diesel::delete(expired).execute(conn)?)
+}
+
+/// What [`prune_streams`] did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct PruneReport {
+    /// Events that finished and were moved to the archive
+    pub archived: usize,
+    /// Archived events older than the retention period that were deleted
+    pub purged: usize,
+}
+
+/// Archive the finished events, then delete the archived ones older than `retention`
+///
+/// # Arguments
+/// * `catalogue` - The sports, for how long the events last
+/// * `now` - The current time
+/// * `retention` - How long archived events are kept, `None` keeps them forever
+pub fn prune_streams(
+    conn: &mut SqliteConnection,
+    catalogue: &Catalogue,
+    now: chrono::NaiveDateTime,
+    retention: Option<chrono::Duration>,
+) -> Result<PruneReport, anyhow::Error> {
+    conn.transaction(|conn| {
+        let archived = archive_past_streams(conn, catalogue, now)?;
+        let purged = match retention {
+            Some(retention) => purge_archived_streams(conn, now - retention)?,
+            None => 0,
+        };
+
+        Ok(PruneReport { archived, purged })
+    })
+}
+
+/// The archived events that started between `from` and `to`, both optional, by start time
+pub fn get_archived_streams(
+    conn: &mut SqliteConnection,
+    from: Option<chrono::NaiveDateTime>,
+    to: Option<chrono::NaiveDateTime>,
+) -> Result<Vec<Stream>, anyhow::Error> {
+    let mut query = stream.filter(archived_at.is_not_null()).into_boxed();
+
+    if let Some(from) = from {
+        query = query.filter(start_time.ge(from));
+    }
+
+    if let Some(to) = to {
+        query = query.filter(start_time.le(to));
+    }
+
+    Ok(query
+        .order((start_time.asc(), schema::stream::id.asc()))
+        .load::<Stream>(conn)?)
+}
+
+/// The events that are live at `now`, by start time
+pub fn get_live_streams(
+    conn: &mut SqliteConnection,
+    catalogue: &Catalogue,
+    now: chrono::NaiveDateTime,
+) -> Result<Vec<Stream>, anyhow::Error> {
+    // nothing that started before this can still be live, the exact check depends on the sport
+    let earliest = now - catalogue.longest_duration();
+
+    Ok(current_streams()
+        .filter(start_time.le(now))
+        .filter(start_time.gt(earliest))
+        .order(start_time.asc())
+        .load::<Stream>(conn)?
+        .into_iter()
+        .filter(|s| s.status(catalogue, now) == EventStatus::Live)
+        .collect())
+}
+
+/// The events that haven't started at `now`, soonest first
+pub fn get_upcoming_streams(
+    conn: &mut SqliteConnection,
+    now: chrono::NaiveDateTime,
+) -> Result<Vec<Stream>, anyhow::Error> {
+    Ok(current_streams()
+        .filter(start_time.gt(now))
+        .order(start_time.asc())
+        .load::<Stream>(conn)?)
+}
+
+/// When the next event starts after `now`, `None` if no event is coming up
+pub fn get_next_kickoff(
+    conn: &mut SqliteConnection,
+    now: chrono::NaiveDateTime,
+) -> Result<Option<chrono::NaiveDateTime>, anyhow::Error> {
+    Ok(current_streams()
+        .filter(start_time.gt(now))
+        .select(diesel::dsl::min(start_time))
+        .first::<Option<chrono::NaiveDateTime>>(conn)?)
 }
 
 pub fn get_streams_by_sport(conn: &mut SqliteConnection, search_sport: String) -> Result<Vec<Stream>, anyhow::Error> {
-    Ok(stream
+    Ok(current_streams()
         .filter(schema::stream::sport.eq(search_sport))
         .load::<Stream>(conn)?)
 }
@@ -80,8 +663,14 @@ pub fn get_streams_by_home_team(
     conn: &mut SqliteConnection,
     search_home_team: String,
 ) -> Result<Vec<Stream>, anyhow::Error> {
-    Ok(stream
-        .filter(schema::stream::home.eq(search_home_team))
+    let team_id = find_team(conn, &search_home_team)?.map(|t| t.id);
+
+    Ok(current_streams()
+        .filter(
+            schema::stream::home
+                .eq(search_home_team)
+                .or(home_team_id.eq(team_id)),
+        )
         .load::<Stream>(conn)?)
 }
 
@@ -89,8 +678,14 @@ pub fn get_streams_by_away_team(
     conn: &mut SqliteConnection,
     search_away_team: String,
 ) -> Result<Vec<Stream>, anyhow::Error> {
-    Ok(stream
-        .filter(schema::stream::away.eq(search_away_team))
+    let team_id = find_team(conn, &search_away_team)?.map(|t| t.id);
+
+    Ok(current_streams()
+        .filter(
+            schema::stream::away
+                .eq(search_away_team)
+                .or(away_team_id.eq(team_id)),
+        )
         .load::<Stream>(conn)?)
 }
 
@@ -98,15 +693,636 @@ pub fn get_streams_by_either_team(
     conn: &mut SqliteConnection,
     search_team: String,
 ) -> Result<Vec<Stream>, anyhow::Error> {
-    Ok(stream
+    let team_id = find_team(conn, &search_team)?.map(|t| t.id);
+
+    Ok(current_streams()
         .filter(
             schema::stream::home
                 .eq(search_team.clone())
-                .or(schema::stream::away.eq(search_team)),
+                .or(schema::stream::away.eq(search_team))
+                .or(home_team_id.eq(team_id))
+                .or(away_team_id.eq(team_id)),
         )
         .load::<Stream>(conn)?)
 }
 
+pub fn get_teams(conn: &mut SqliteConnection) -> Result<Vec<Team>, anyhow::Error> {
+    Ok(teams::table.order(teams::name.asc()).load::<Team>(conn)?)
+}
+
+/// Find the team known as `team_name`, by any of its aliases
+///
+/// # Arguments
+/// * `team_name` - Any spelling of the team, case, accents and look-alike letters don't matter
+pub fn find_team(conn: &mut SqliteConnection, team_name: &str) -> Result<Option<Team>, anyhow::Error> {
+    let key = search::normalize(team_name);
+
+    Ok(team_aliases::table
+        .inner_join(teams::table)
+        .filter(team_aliases::alias.eq(key))
+        .select((teams::id, teams::name))
+        .first::<Team>(conn)
+        .optional()?)
+}
+
+/// Get the team named exactly `team_name`, creating it if it doesn't exist yet
+fn get_or_create_team(conn: &mut SqliteConnection, team_name: &str) -> Result<Team, anyhow::Error> {
+    diesel::insert_or_ignore_into(teams::table)
+        .values(&TeamNew { name: team_name })
+        .execute(conn)?;
+
+    Ok(teams::table.filter(teams::name.eq(team_name)).first::<Team>(conn)?)
+}
+
+/// Point the (already normalized) alias `key` to `team_id`.
+/// If the alias pointed to a team that was only created from that spelling, that team is merged into `team_id`
+fn set_alias(conn: &mut SqliteConnection, key: &str, team_id: i32) -> Result<(), anyhow::Error> {
+    let previous = team_aliases::table
+        .inner_join(teams::table)
+        .filter(team_aliases::alias.eq(key))
+        .select((teams::id, teams::name))
+        .first::<Team>(conn)
+        .optional()?;
+
+    diesel::insert_into(team_aliases::table)
+        .values((team_aliases::alias.eq(key), team_aliases::team_id.eq(team_id)))
+        .on_conflict(team_aliases::alias)
+        .do_update()
+        .set(team_aliases::team_id.eq(team_id))
+        .execute(conn)?;
+
+    let Some(previous) = previous else {
+        return Ok(());
+    };
+
+    if previous.id == team_id || search::normalize(&previous.name) != key {
+        return Ok(());
+    }
+
+    // the old team was just another spelling, so everything it had now belongs to the new one
+    merge_team(conn, previous.id, team_id)
+}
+
+/// Move the events and the aliases of the team `from` to the team `into`, then delete `from`
+fn merge_team(conn: &mut SqliteConnection, from: i32, into: i32) -> Result<(), anyhow::Error> {
+    diesel::update(stream.filter(home_team_id.eq(from)))
+        .set(home_team_id.eq(into))
+        .execute(conn)?;
+    diesel::update(stream.filter(away_team_id.eq(from)))
+        .set(away_team_id.eq(into))
+        .execute(conn)?;
+    diesel::update(team_aliases::table.filter(team_aliases::team_id.eq(from)))
+        .set(team_aliases::team_id.eq(into))
+        .execute(conn)?;
+    diesel::delete(teams::table.filter(teams::id.eq(from))).execute(conn)?;
+
+    Ok(())
+}
+
+/// The migration creates a team for every name of the existing events, but sql can't normalize them into aliases.
+/// Every team without an alias gets its own name as one, so the seeded aliases merge it like a scraped spelling,
+/// a team whose name is already the alias of another one is merged into it.
+fn alias_unaliased_teams(conn: &mut SqliteConnection) -> Result<(), anyhow::Error> {
+    let unaliased = teams::table
+        .left_join(team_aliases::table)
+        .filter(team_aliases::alias.is_null())
+        .select((teams::id, teams::name))
+        .load::<Team>(conn)?;
+
+    for team in unaliased {
+        let key = search::normalize(&team.name);
+
+        if key.is_empty() {
+            continue;
+        }
+
+        match find_team(conn, &team.name)? {
+            Some(owner) => merge_team(conn, team.id, owner.id)?,
+            None => {
+                diesel::insert_into(team_aliases::table)
+                    .values((team_aliases::alias.eq(key), team_aliases::team_id.eq(team.id)))
+                    .execute(conn)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Make `alias` a spelling of the team `team_name`, creating the team if needed
+///
+/// # Arguments
+/// * `team_name` - The canonical name of the team
+/// * `alias` - Another name of the team
+pub fn add_team_alias(conn: &mut SqliteConnection, team_name: &str, alias: &str) -> Result<Team, anyhow::Error> {
+    conn.transaction(|conn| {
+        let team = get_or_create_team(conn, team_name)?;
+
+        set_alias(conn, &search::normalize(team_name), team.id)?;
+        set_alias(conn, &search::normalize(alias), team.id)?;
+
+        Ok(team)
+    })
+}
+
+/// Save every team and its aliases, the later entries win if two of them share an alias
+///
+/// # Arguments
+/// * `all_teams` - The teams to save, see [`crate::config::Config::team_aliases`]
+pub fn seed_teams(conn: &mut SqliteConnection, all_teams: &[TeamAliases]) -> Result<(), anyhow::Error> {
+    conn.transaction(|conn| {
+        alias_unaliased_teams(conn)?;
+
+        for entry in all_teams {
+            let team = get_or_create_team(conn, &entry.name)?;
+
+            set_alias(conn, &search::normalize(&entry.name), team.id)?;
+
+            for alias in &entry.aliases {
+                set_alias(conn, &search::normalize(alias), team.id)?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// The id of the team a scraped name belongs to, a team is created for names we have never seen
+///
+/// # Arguments
+/// * `team_name` - The name as it is on the website
+pub fn resolve_team(conn: &mut SqliteConnection, team_name: &str) -> Result<Option<i32>, anyhow::Error> {
+    let key = search::normalize(team_name);
+
+    if key.is_empty() {
+        return Ok(None);
+    }
+
+    if let Some(team) = find_team(conn, team_name)? {
+        return Ok(Some(team.id));
+    }
+
+    let team = get_or_create_team(conn, team_name.trim())?;
+    set_alias(conn, &key, team.id)?;
+
+    Ok(Some(team.id))
+}
+
+/// Search the events by team name, ignoring case, accents and look-alike letters.
+/// The events are ordered by how well their best team matches `query`, best match first.
+///
+/// # Arguments
+/// * `query` - The team to look for, like "man utd"
+/// * `threshold` - The lowest score (from 0 to 1) of the returned events, see [`search::DEFAULT_THRESHOLD`]
+pub fn search_streams(
+    conn: &mut SqliteConnection,
+    query: &str,
+    threshold: f64,
+) -> Result<Vec<(Stream, f64)>, anyhow::Error> {
+    // sqlite can't normalize the names, so we score every event here
+    let mut matches: Vec<(Stream, f64)> = current_streams()
+        .load::<Stream>(conn)?
+        .into_iter()
+        .map(|s| {
+            let best = search::score(query, &s.home).max(search::score(query, &s.away));
+            (s, best)
+        })
+        .filter(|(_, best)| *best >= threshold)
+        .collect();
+
+    matches.sort_by(|(a, a_score), (b, b_score)| {
+        b_score
+            .total_cmp(a_score)
+            .then_with(|| a.start_time.cmp(&b.start_time))
+    });
+
+    Ok(matches)
+}
+
+/// Which way [`get_filtered_streams`] orders the events by start time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// Where a page of [`get_filtered_streams`] ended, so the next page can carry on from there
+/// even if events were added in between. Formatted as `<start timestamp>_<id>`.
+///
+/// # Examples
+/// ```
+/// use scraper::db::helpers::StreamCursor;
+///
+/// let cursor: StreamCursor = "1706745600_42".parse().unwrap();
+/// assert_eq!(cursor.id, 42);
+/// assert_eq!(cursor.to_string(), "1706745600_42");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamCursor {
+    pub start_time: chrono::NaiveDateTime,
+    pub id: i32,
+}
+
+impl StreamCursor {
+    /// The cursor pointing right after `s`
+    pub fn after(s: &Stream) -> Option<StreamCursor> {
+        Some(StreamCursor {
+            start_time: s.start_time,
+            id: s.id?,
+        })
+    }
+}
+
+impl std::fmt::Display for StreamCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}_{}", self.start_time.timestamp(), self.id)
+    }
+}
+
+impl std::str::FromStr for StreamCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (time, cursor_id) = s.split_once('_').ok_or(anyhow::anyhow!("invalid cursor {}", s))?;
+
+        Ok(StreamCursor {
+            start_time: chrono::NaiveDateTime::from_timestamp_opt(time.parse()?, 0)
+                .ok_or(anyhow::anyhow!("invalid cursor {}", s))?,
+            id: cursor_id.parse()?,
+        })
+    }
+}
+
+/// How many events `/streams` returns when no limit is given
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// The most events `/streams` returns in one page
+pub const MAX_PAGE_SIZE: i64 = 500;
+
+/// The page size of `/streams` and of the `streams` graphql query, `limit` defaults to [`DEFAULT_PAGE_SIZE`]
+/// and can't be more than [`MAX_PAGE_SIZE`]
+///
+/// # Returns
+/// The limit to use, or why the request is invalid
+///
+/// # Example
+/// ```
+/// use scraper::db::helpers::page_size;
+///
+/// assert_eq!(page_size(None, None), Ok(50));
+/// assert!(page_size(Some(-1), None).is_err());
+/// assert!(page_size(Some(10), Some(-5)).is_err());
+/// ```
+pub fn page_size(limit: Option<i64>, offset: Option<i64>) -> Result<i64, String> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(format!("invalid limit {}, expected 1 to {}", limit, MAX_PAGE_SIZE));
+    }
+
+    if offset.is_some_and(|skip| skip < 0) {
+        return Err("offset can't be negative".to_owned());
+    }
+
+    Ok(limit)
+}
+
+/// Filters for [`get_filtered_streams`], every filter that is set has to match.
+/// They can be chained, so "football in England starting in the next 2 hours with links" is
+///
+/// ```
+/// use chrono::{Duration, Utc};
+/// use scraper::db::helpers::StreamFilter;
+///
+/// let now = Utc::now().naive_utc();
+///
+/// let filter = StreamFilter::new()
+///     .sport("Football")
+///     .country("england")
+///     .starts_between(now, now + Duration::hours(2))
+///     .has_links(true)
+///     .limit(20);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StreamFilter {
+    pub sport: Option<String>,
+    pub league: Option<String>,
+    pub country: Option<String>,
+    /// matches either the home or the away team
+    pub team: Option<String>,
+    /// only events starting at or after this time
+    pub starts_after: Option<chrono::NaiveDateTime>,
+    /// only events starting at or before this time
+    pub starts_before: Option<chrono::NaiveDateTime>,
+    /// only events with (or without) stream links
+    pub has_links: Option<bool>,
+    pub order: SortOrder,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    /// only events after this one, in the sort order
+    pub cursor: Option<StreamCursor>,
+}
+
+impl StreamFilter {
+    pub fn new() -> StreamFilter {
+        StreamFilter::default()
+    }
+
+    pub fn sport(mut self, search_sport: impl Into<String>) -> StreamFilter {
+        self.sport = Some(search_sport.into());
+        self
+    }
+
+    pub fn league(mut self, search_league: impl Into<String>) -> StreamFilter {
+        self.league = Some(search_league.into());
+        self
+    }
+
+    pub fn country(mut self, search_country: impl Into<String>) -> StreamFilter {
+        self.country = Some(search_country.into());
+        self
+    }
+
+    pub fn team(mut self, search_team: impl Into<String>) -> StreamFilter {
+        self.team = Some(search_team.into());
+        self
+    }
+
+    pub fn starts_between(mut self, after: chrono::NaiveDateTime, before: chrono::NaiveDateTime) -> StreamFilter {
+        self.starts_after = Some(after);
+        self.starts_before = Some(before);
+        self
+    }
+
+    pub fn has_links(mut self, with_links: bool) -> StreamFilter {
+        self.has_links = Some(with_links);
+        self
+    }
+
+    pub fn order(mut self, sort_order: SortOrder) -> StreamFilter {
+        self.order = sort_order;
+        self
+    }
+
+    pub fn limit(mut self, max: i64) -> StreamFilter {
+        self.limit = Some(max);
+        self
+    }
+
+    pub fn offset(mut self, skip: i64) -> StreamFilter {
+        self.offset = Some(skip);
+        self
+    }
+
+    pub fn cursor(mut self, after: StreamCursor) -> StreamFilter {
+        self.cursor = Some(after);
+        self
+    }
+}
+
+pub fn get_filtered_streams(conn: &mut SqliteConnection, filter: &StreamFilter) -> Result<Vec<Stream>, anyhow::Error> {
+    let mut query = current_streams();
+
+    if let Some(search_sport) = &filter.sport {
+        query = query.filter(schema::stream::sport.eq(search_sport));
+    }
+
+    if let Some(search_league) = &filter.league {
+        query = query.filter(schema::stream::league.eq(search_league));
+    }
+
+    if let Some(search_country) = &filter.country {
+        query = query.filter(schema::stream::country.eq(search_country));
+    }
+
+    if let Some(search_team) = &filter.team {
+        let team_id = find_team(conn, search_team)?.map(|t| t.id);
+
+        query = query.filter(
+            schema::stream::home
+                .eq(search_team)
+                .or(schema::stream::away.eq(search_team))
+                .or(home_team_id.eq(team_id))
+                .or(away_team_id.eq(team_id)),
+        );
+    }
+
+    if let Some(after) = filter.starts_after {
+        query = query.filter(start_time.ge(after));
+    }
+
+    if let Some(before) = filter.starts_before {
+        query = query.filter(start_time.le(before));
+    }
+
+    match filter.has_links {
+        Some(true) => {
+            query = query.filter(schema::stream::id.eq_any(current_links().select(links::event_id.nullable())));
+        }
+        Some(false) => {
+            query = query.filter(diesel::dsl::not(
+                schema::stream::id.eq_any(current_links().select(links::event_id.nullable())),
+            ));
+        }
+        None => {}
+    }
+
+    let stream_id = schema::stream::id.assume_not_null();
+
+    if let Some(cursor) = filter.cursor {
+        query = match filter.order {
+            SortOrder::Ascending => query.filter(
+                start_time
+                    .gt(cursor.start_time)
+                    .or(start_time.eq(cursor.start_time).and(stream_id.gt(cursor.id))),
+            ),
+            SortOrder::Descending => query.filter(
+                start_time
+                    .lt(cursor.start_time)
+                    .or(start_time.eq(cursor.start_time).and(stream_id.lt(cursor.id))),
+            ),
+        };
+    }
+
+    query = match filter.order {
+        SortOrder::Ascending => query.order((start_time.asc(), stream_id.asc())),
+        SortOrder::Descending => query.order((start_time.desc(), stream_id.desc())),
+    };
+
+    if let Some(max) = filter.limit {
+        query = query.limit(max);
+    }
+
+    if let Some(skip) = filter.offset {
+        // sqlite needs a limit to use an offset, -1 means no limit
+        if filter.limit.is_none() {
+            query = query.limit(-1);
+        }
+
+        query = query.offset(skip);
+    }
+
+    Ok(query.load::<Stream>(conn)?)
+}
+
+/// The `scrape_meta` key of the utc offset the last schedule was read in, like `+01:00`
+pub const META_UTC_OFFSET: &str = "utc_offset";
+
+/// Remember `value` under `key` in `scrape_meta`, replacing what was there
+///
+/// # Arguments
+/// * `key` - The name of the value, see [`META_UTC_OFFSET`]
+/// * `value` - What to remember
+/// * `now` - When it was set
+pub fn set_meta(
+    conn: &mut SqliteConnection,
+    key: &str,
+    value: &str,
+    now: chrono::NaiveDateTime,
+) -> Result<usize, anyhow::Error> {
+    Ok(diesel::insert_into(scrape_meta::table)
+        .values((
+            scrape_meta::key.eq(key),
+            scrape_meta::value.eq(value),
+            scrape_meta::updated_at.eq(now),
+        ))
+        .on_conflict(scrape_meta::key)
+        .do_update()
+        .set((scrape_meta::value.eq(value), scrape_meta::updated_at.eq(now)))
+        .execute(conn)?)
+}
+
+/// The `scrape_meta` key of the last mirror of `sport_name` that worked
+///
+/// # Example
+/// ```
+/// use scraper::db::helpers::mirror_meta_key;
+///
+/// assert_eq!(mirror_meta_key("Football"), "mirror:Football");
+/// ```
+pub fn mirror_meta_key(sport_name: &str) -> String {
+    format!("mirror:{}", sport_name)
+}
+
+/// The last mirror of `sport_name` that worked, if one ever did
+pub fn get_working_mirror(conn: &mut SqliteConnection, sport_name: &str) -> Result<Option<String>, anyhow::Error> {
+    get_meta(conn, &mirror_meta_key(sport_name))
+}
+
+/// How the mirrors of `sport_name` did, or of every sport if `None`, best first
+pub fn get_mirror_health(conn: &mut SqliteConnection, sport_name: Option<&str>) -> Result<Vec<MirrorHealth>, Error> {
+    let mut query = mirror_health::table.into_boxed();
+
+    if let Some(sport_name) = sport_name {
+        query = query.filter(mirror_health::sport.eq(sport_name.to_owned()));
+    }
+
+    Ok(query
+        .order((
+            mirror_health::sport,
+            mirror_health::consecutive_failures,
+            mirror_health::last_success.desc(),
+        ))
+        .load::<MirrorHealth>(conn)?)
+}
+
+/// Remember that `mirror_url` worked for `sport_name`, it is tried first next time
+///
+/// # Arguments
+/// * `sport_name` - The name of the sport
+/// * `mirror_url` - The mirror that worked
+/// * `now` - When it worked
+pub fn record_mirror_success(
+    conn: &mut SqliteConnection,
+    sport_name: &str,
+    mirror_url: &str,
+    now: chrono::NaiveDateTime,
+) -> Result<(), Error> {
+    conn.transaction(|conn| {
+        diesel::insert_into(mirror_health::table)
+            .values((
+                mirror_health::sport.eq(sport_name),
+                mirror_health::url.eq(mirror_url),
+                mirror_health::successes.eq(1),
+                mirror_health::last_success.eq(now),
+            ))
+            .on_conflict((mirror_health::sport, mirror_health::url))
+            .do_update()
+            .set((
+                mirror_health::successes.eq(mirror_health::successes + 1),
+                mirror_health::consecutive_failures.eq(0),
+                mirror_health::last_success.eq(now),
+            ))
+            .execute(conn)?;
+
+        set_meta(conn, &mirror_meta_key(sport_name), mirror_url, now)?;
+
+        Ok(())
+    })
+}
+
+/// Remember that `mirror_url` failed for `sport_name`, a mirror that keeps failing is tried last
+///
+/// # Arguments
+/// * `sport_name` - The name of the sport
+/// * `mirror_url` - The mirror that failed
+/// * `error` - Why it failed
+/// * `now` - When it failed
+pub fn record_mirror_failure(
+    conn: &mut SqliteConnection,
+    sport_name: &str,
+    mirror_url: &str,
+    error: &str,
+    now: chrono::NaiveDateTime,
+) -> Result<(), Error> {
+    diesel::insert_into(mirror_health::table)
+        .values((
+            mirror_health::sport.eq(sport_name),
+            mirror_health::url.eq(mirror_url),
+            mirror_health::failures.eq(1),
+            mirror_health::consecutive_failures.eq(1),
+            mirror_health::last_failure.eq(now),
+            mirror_health::last_error.eq(error),
+        ))
+        .on_conflict((mirror_health::sport, mirror_health::url))
+        .do_update()
+        .set((
+            mirror_health::failures.eq(mirror_health::failures + 1),
+            mirror_health::consecutive_failures.eq(mirror_health::consecutive_failures + 1),
+            mirror_health::last_failure.eq(now),
+            mirror_health::last_error.eq(error),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Remember a mirror of `sport_name` found on the landing page, so it is tried even if it isn't in the config
+pub fn add_discovered_mirror(conn: &mut SqliteConnection, sport_name: &str, mirror_url: &str) -> Result<(), Error> {
+    diesel::insert_into(mirror_health::table)
+        .values((
+            mirror_health::sport.eq(sport_name),
+            mirror_health::url.eq(mirror_url),
+            mirror_health::discovered.eq(true),
+        ))
+        .on_conflict((mirror_health::sport, mirror_health::url))
+        .do_update()
+        .set(mirror_health::discovered.eq(true))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// The value remembered under `key`, if there is one
+pub fn get_meta(conn: &mut SqliteConnection, key: &str) -> Result<Option<String>, anyhow::Error> {
+    Ok(scrape_meta::table
+        .filter(scrape_meta::key.eq(key))
+        .select(scrape_meta::value)
+        .first::<String>(conn)
+        .optional()?)
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct LeagueWithCountry {
     pub league: String,
@@ -117,6 +1333,7 @@ pub fn get_unique_leagues_with_country(conn: &mut SqliteConnection) -> Result<Ve
     let mut leagues = Vec::new();
 
     let mut results = stream
+        .filter(archived_at.is_null())
         .select((schema::stream::league, schema::stream::country))
         .distinct()
         .load::<(String, String)>(conn)?;
@@ -139,15 +1356,93 @@ pub fn get_unique_leagues_with_country(conn: &mut SqliteConnection) -> Result<Ve
 }
 
 pub fn get_active_games(conn: &mut SqliteConnection) -> Result<Vec<Stream>, anyhow::Error> {
-    Ok(stream.filter(schema::stream::stream_link.ne("")).load::<Stream>(conn)?)
+    get_linked_streams(conn)
 }
 
 pub fn delete_all_streams(conn: &mut SqliteConnection) -> Result<usize, anyhow::Error> {
+    diesel::delete(link_sightings::table).execute(conn)?;
+    diesel::delete(links::table).execute(conn)?;
+
     Ok(diesel::delete(stream).execute(conn)?)
 }
 
 pub fn get_streams_by_league(conn: &mut SqliteConnection, search_league: String) -> Result<Vec<Stream>, anyhow::Error> {
-    Ok(stream
+    Ok(current_streams()
         .filter(schema::stream::league.eq(search_league))
         .load::<Stream>(conn)?)
 }
+
+/// The `kind` of the runs of [`crate::scrape::scrape_events`]
+pub const RUN_EVENTS: &str = "events";
+
+/// The `kind` of the runs of [`crate::scrape::update_streams`]
+pub const RUN_LINKS: &str = "links";
+
+/// The `kind` of the runs of [`crate::scrape::start_scraping`], events then links
+pub const RUN_FULL: &str = "full";
+
+/// Record that a scrape started, finish it with [`finish_scrape_run`]
+///
+/// # Arguments
+/// * `kind` - What is scraped, see [`RUN_EVENTS`]
+/// * `now` - When the scrape started
+///
+/// # Returns
+/// The id of the run
+pub fn start_scrape_run(conn: &mut SqliteConnection, kind: &str, now: chrono::NaiveDateTime) -> Result<i32, Error> {
+    conn.transaction(|conn| {
+        diesel::insert_into(scrape_runs::table)
+            .values((scrape_runs::kind.eq(kind), scrape_runs::started_at.eq(now)))
+            .execute(conn)?;
+
+        scrape_runs::table
+            .select(diesel::dsl::max(scrape_runs::id))
+            .first::<Option<i32>>(conn)?
+            .ok_or(anyhow::anyhow!("the scrape run was not saved"))
+    })
+}
+
+/// Record what a scrape found and when it ended
+///
+/// # Arguments
+/// * `run_id` - The id returned by [`start_scrape_run`]
+/// * `report` - What the scrape found
+/// * `now` - When the scrape ended
+pub fn finish_scrape_run(
+    conn: &mut SqliteConnection,
+    run_id: i32,
+    report: &ScrapeReport,
+    now: chrono::NaiveDateTime,
+) -> Result<(), Error> {
+    diesel::update(scrape_runs::table.filter(scrape_runs::id.eq(run_id)))
+        .set((
+            scrape_runs::finished_at.eq(now),
+            scrape_runs::events_found.eq(report.events_found() as i32),
+            scrape_runs::links_found.eq(report.links_found as i32),
+            scrape_runs::sport_counts.eq(serde_json::to_string(&report.sports)?),
+            scrape_runs::errors.eq(serde_json::to_string(&report.errors)?),
+            scrape_runs::succeeded.eq(report.is_success()),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// The latest `limit` scrapes, newest first
+pub fn get_scrape_runs(conn: &mut SqliteConnection, limit: i64) -> Result<Vec<ScrapeRun>, Error> {
+    Ok(scrape_runs::table
+        .order((scrape_runs::started_at.desc(), scrape_runs::id.desc()))
+        .limit(limit)
+        .load::<ScrapeRun>(conn)?)
+}
+
+/// The latest scrape of one of `kinds` that succeeded, see [`ScrapeReport::is_success`]
+pub fn get_last_successful_scrape(conn: &mut SqliteConnection, kinds: &[&str]) -> Result<Option<ScrapeRun>, Error> {
+    Ok(scrape_runs::table
+        .filter(scrape_runs::kind.eq_any(kinds))
+        .filter(scrape_runs::finished_at.is_not_null())
+        .filter(scrape_runs::succeeded.eq(true))
+        .order((scrape_runs::started_at.desc(), scrape_runs::id.desc()))
+        .first::<ScrapeRun>(conn)
+        .optional()?)
+}
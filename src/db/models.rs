@@ -1,9 +1,14 @@
 //! This module contains the models for the diesel ORM
 
-use std::fmt::Debug;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug},
+};
 
 use diesel::prelude::*;
-use serde::{ser::SerializeStruct, Serialize};
+use serde::{ser::SerializeStruct, Deserialize, Serialize};
+
+use crate::{constants::sports::Catalogue, retry::GaveUp};
 
 #[derive(Debug, Queryable, Clone)]
 pub struct Stream {
@@ -14,8 +19,11 @@ pub struct Stream {
     pub league: String,
     pub country: String,
     pub url: String,
-    pub stream_link: String,
     pub sport: String,
+    pub home_team_id: Option<i32>,
+    pub away_team_id: Option<i32>,
+    /// when the event was moved to the archive, `None` for current events
+    pub archived_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Debug, Insertable, Clone)]
@@ -27,8 +35,341 @@ pub struct StreamNew<'a> {
     pub league: &'a str,
     pub country: &'a str,
     pub url: &'a str,
-    pub stream_link: &'a str,
     pub sport: &'a str,
+    pub home_team_id: Option<i32>,
+    pub away_team_id: Option<i32>,
+}
+
+/// The canonical identity of a team, every spelling of it points here through `team_aliases`
+#[derive(Debug, Queryable, Clone, Serialize, PartialEq, Eq)]
+pub struct Team {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Insertable, Clone)]
+#[diesel(table_name = crate::db::schema::teams)]
+pub struct TeamNew<'a> {
+    pub name: &'a str,
+}
+
+/// Where an event is in its life, computed from its start time and how long its sport usually lasts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventStatus {
+    Upcoming,
+    Live,
+    /// the event most likely finished, we can't know for sure
+    Finished,
+}
+
+impl EventStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventStatus::Upcoming => "upcoming",
+            EventStatus::Live => "live",
+            EventStatus::Finished => "finished",
+        }
+    }
+}
+
+impl Stream {
+    /// The status of the event at `now`
+    ///
+    /// # Arguments
+    /// * `catalogue` - The sports, for how long the event lasts
+    /// * `now` - The time to get the status at, usually `Utc::now().naive_utc()`
+    pub fn status(&self, catalogue: &Catalogue, now: chrono::NaiveDateTime) -> EventStatus {
+        if now < self.start_time {
+            EventStatus::Upcoming
+        } else if now < self.start_time + catalogue.typical_duration(&self.sport) {
+            EventStatus::Live
+        } else {
+            EventStatus::Finished
+        }
+    }
+}
+
+/// A single stream link of an event, stored in the `links` table
+#[derive(Debug, Queryable, Clone)]
+pub struct Link {
+    pub id: Option<i32>,
+    pub event_id: i32,
+    pub url: String,
+    pub position: i32,
+    pub first_seen: chrono::NaiveDateTime,
+    pub last_seen: chrono::NaiveDateTime,
+    pub streamer: Option<String>,
+    pub language: Option<String>,
+    pub quality: Option<String>,
+    pub ad_count: Option<i32>,
+    pub channel_type: Option<String>,
+    /// when the link disappeared from the page, `None` while it is still there
+    pub removed_at: Option<chrono::NaiveDateTime>,
+    /// whether the link worked the last time it was checked, `None` if it never was
+    pub reachable: Option<bool>,
+    pub http_status: Option<i32>,
+    /// where the link ended up after the redirects
+    pub final_url: Option<String>,
+    /// the kind of player behind the link, see [`crate::link_checker::PlayerType`]
+    pub player_type: Option<String>,
+    pub checked_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable, Clone)]
+#[diesel(table_name = crate::db::schema::links)]
+pub struct LinkNew<'a> {
+    pub event_id: i32,
+    pub url: &'a str,
+    pub position: i32,
+    pub first_seen: chrono::NaiveDateTime,
+    pub last_seen: chrono::NaiveDateTime,
+    pub streamer: Option<&'a str>,
+    pub language: Option<&'a str>,
+    pub quality: Option<&'a str>,
+    pub ad_count: Option<i32>,
+    pub channel_type: Option<&'a str>,
+}
+
+/// The result of opening a link, saved on the link by [`crate::db::helpers::save_link_check`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, AsChangeset)]
+#[diesel(table_name = crate::db::schema::links, treat_none_as_null = true)]
+pub struct LinkCheck {
+    /// `None` when the link can't be checked, like `acestream://` links
+    pub reachable: Option<bool>,
+    pub http_status: Option<i32>,
+    pub final_url: Option<String>,
+    pub player_type: Option<String>,
+}
+
+/// What part of a scrape failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureKind {
+    /// The schedule page of a sport, none of its games were saved
+    Sport,
+    /// A single game of a schedule, the other games were saved
+    Game,
+    /// The page of an event we look for links on
+    Links,
+    /// The whole scrape stopped
+    Run,
+    /// The landing page we look for the current mirrors on
+    Discovery,
+    /// Saved before the failures had a kind, only the message is known
+    Unknown,
+}
+
+/// Something a scrape couldn't do, the scrape carries on without it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrapeFailure {
+    pub kind: FailureKind,
+    pub sport: Option<String>,
+    /// The page that failed, or the link of the game
+    pub url: Option<String>,
+    /// How many times it was tried
+    pub attempts: u32,
+    pub error: String,
+}
+
+impl ScrapeFailure {
+    /// # Arguments
+    /// * `kind` - What failed
+    /// * `sport` - The sport it belongs to, if any
+    /// * `url` - The page that failed, if we know it
+    /// * `error` - Why, a [`GaveUp`] gives the number of attempts
+    pub fn new(kind: FailureKind, sport: Option<&str>, url: Option<&str>, error: &anyhow::Error) -> ScrapeFailure {
+        let (attempts, error) = match error.downcast_ref::<GaveUp>() {
+            Some(gave_up) => (gave_up.attempts, gave_up.error.to_string()),
+            None => (1, error.to_string()),
+        };
+
+        ScrapeFailure {
+            kind,
+            sport: sport.map(str::to_owned),
+            url: url.map(str::to_owned),
+            attempts,
+            error,
+        }
+    }
+}
+
+impl fmt::Display for ScrapeFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in [&self.sport, &self.url].into_iter().flatten() {
+            write!(f, "{}: ", part)?;
+        }
+
+        write!(f, "{}", self.error)?;
+
+        if self.attempts > 1 {
+            write!(f, " (after {} attempts)", self.attempts)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// What a scrape found, saved in the `scrape_runs` table once it is done
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ScrapeReport {
+    /// How many events every sport had
+    pub sports: BTreeMap<String, usize>,
+    pub links_found: usize,
+    pub errors: Vec<ScrapeFailure>,
+}
+
+impl ScrapeReport {
+    pub fn events_found(&self) -> usize {
+        self.sports.values().sum()
+    }
+
+    /// The scrape didn't stop halfway and didn't fail at everything it tried,
+    /// losing a dead sport or a broken game still counts
+    pub fn is_success(&self) -> bool {
+        let aborted = self.errors.iter().any(|e| e.kind == FailureKind::Run);
+        let found_something = self.events_found() > 0 || self.links_found > 0;
+
+        !aborted && (found_something || self.errors.is_empty())
+    }
+
+    /// Add what another scrape found to this one
+    pub fn merge(&mut self, other: ScrapeReport) {
+        self.sports.extend(other.sports);
+        self.links_found += other.links_found;
+        self.errors.extend(other.errors);
+    }
+}
+
+/// A scrape, as saved in the `scrape_runs` table
+#[derive(Debug, Queryable, Clone, PartialEq)]
+pub struct ScrapeRun {
+    pub id: i32,
+    /// `events`, `links` or `full`, see [`crate::db::helpers::RUN_EVENTS`]
+    pub kind: String,
+    pub started_at: chrono::NaiveDateTime,
+    /// `None` while the scrape runs, or if it crashed
+    pub finished_at: Option<chrono::NaiveDateTime>,
+    pub events_found: i32,
+    pub links_found: i32,
+    /// json object of sport name to the number of events found
+    pub sport_counts: String,
+    /// json array of [`ScrapeFailure`]
+    pub errors: String,
+    /// see [`ScrapeReport::is_success`]
+    pub succeeded: bool,
+}
+
+impl ScrapeRun {
+    /// How many events every sport had
+    pub fn sports(&self) -> BTreeMap<String, usize> {
+        serde_json::from_str(&self.sport_counts).unwrap_or_default()
+    }
+
+    pub fn errors(&self) -> Vec<ScrapeFailure> {
+        if let Ok(failures) = serde_json::from_str(&self.errors) {
+            return failures;
+        }
+
+        // the older runs saved their errors as plain messages
+        serde_json::from_str::<Vec<String>>(&self.errors)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|error| ScrapeFailure {
+                kind: FailureKind::Unknown,
+                sport: None,
+                url: None,
+                attempts: 1,
+                error,
+            })
+            .collect()
+    }
+
+    /// The scrape finished and succeeded, see [`ScrapeReport::is_success`]
+    pub fn is_success(&self) -> bool {
+        self.finished_at.is_some() && self.succeeded
+    }
+}
+
+/// How a mirror of a sport did, stored in the `mirror_health` table
+#[derive(Debug, Queryable, Clone, PartialEq)]
+pub struct MirrorHealth {
+    pub sport: String,
+    pub url: String,
+    pub successes: i32,
+    pub failures: i32,
+    /// how many times in a row it failed, reset by every success
+    pub consecutive_failures: i32,
+    pub last_success: Option<chrono::NaiveDateTime>,
+    pub last_failure: Option<chrono::NaiveDateTime>,
+    pub last_error: Option<String>,
+    /// found on the landing page instead of the config
+    pub discovered: bool,
+}
+
+/// A stretch of time a link was on the event page, stored in the `link_sightings` table
+#[derive(Debug, Queryable, Clone, PartialEq)]
+pub struct LinkSighting {
+    pub id: i32,
+    pub link_id: i32,
+    pub first_seen: chrono::NaiveDateTime,
+    pub last_seen: chrono::NaiveDateTime,
+    /// when we noticed the link was gone, `None` if it is still on the page
+    pub removed_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable, Clone)]
+#[diesel(table_name = crate::db::schema::link_sightings)]
+pub struct LinkSightingNew {
+    pub link_id: i32,
+    pub first_seen: chrono::NaiveDateTime,
+    pub last_seen: chrono::NaiveDateTime,
+}
+
+/// A link with every stretch of time it was on the page, oldest first
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkHistory {
+    #[serde(flatten)]
+    pub link: Link,
+    pub sightings: Vec<LinkSighting>,
+    /// The link was on the page the whole time, it was never pulled
+    pub stable: bool,
+    /// The link was pulled while the event was live
+    pub pulled_mid_game: bool,
+}
+
+/// How the links of an event changed over time, returned by `/id/<id>/links`
+#[derive(Debug, Clone, Serialize)]
+pub struct EventLinkHistory {
+    pub event_id: i32,
+    /// How many different links were ever on the page
+    pub added: usize,
+    /// How many times a link was pulled from the page
+    pub removed: usize,
+    /// How many times a pulled link came back
+    pub returned: usize,
+    pub links: Vec<LinkHistory>,
+}
+
+/// A row of the link table on an event page, before it is saved to database
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkInfo {
+    pub url: String,
+    pub streamer: Option<String>,
+    pub language: Option<String>,
+    pub quality: Option<String>,
+    pub ad_count: Option<i32>,
+    pub channel_type: Option<String>,
+}
+
+/// An event together with its stream links, this is what the api returns
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamWithLinks {
+    #[serde(flatten)]
+    pub stream: Stream,
+    pub status: EventStatus,
+    pub stream_link: Vec<String>,
+    pub links: Vec<Link>,
 }
 
 impl Serialize for Stream {
@@ -36,8 +377,7 @@ impl Serialize for Stream {
     where
         S: serde::ser::Serializer,
     {
-        let split_streams: Vec<&str> = self.stream_link.split(',').collect();
-        let mut stream = serializer.serialize_struct("Stream", 8)?;
+        let mut stream = serializer.serialize_struct("Stream", 11)?;
         stream.serialize_field("id", &self.id)?;
         stream.serialize_field("home", &self.home)?;
         stream.serialize_field("away", &self.away)?;
@@ -45,72 +385,183 @@ impl Serialize for Stream {
         stream.serialize_field("league", &self.league)?;
         stream.serialize_field("country", &self.country)?;
         stream.serialize_field("url", &self.url)?;
-        stream.serialize_field("stream_link", &split_streams)?;
         stream.serialize_field("sport", &self.sport)?;
+        stream.serialize_field("home_team_id", &self.home_team_id)?;
+        stream.serialize_field("away_team_id", &self.away_team_id)?;
+        stream.serialize_field("archived_at", &self.archived_at.map(|t| t.timestamp()))?;
         stream.end()
     }
 }
 
-mod tests {
-    
+impl Serialize for Link {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        let mut link = serializer.serialize_struct("Link", 14)?;
+        link.serialize_field("url", &self.url)?;
+        link.serialize_field("streamer", &self.streamer)?;
+        link.serialize_field("language", &self.language)?;
+        link.serialize_field("quality", &self.quality)?;
+        link.serialize_field("ad_count", &self.ad_count)?;
+        link.serialize_field("channel_type", &self.channel_type)?;
+        link.serialize_field("first_seen", &self.first_seen.timestamp())?;
+        link.serialize_field("last_seen", &self.last_seen.timestamp())?;
+        link.serialize_field("removed_at", &self.removed_at.map(|t| t.timestamp()))?;
+        link.serialize_field("reachable", &self.reachable)?;
+        link.serialize_field("http_status", &self.http_status)?;
+        link.serialize_field("final_url", &self.final_url)?;
+        link.serialize_field("player_type", &self.player_type)?;
+        link.serialize_field("checked_at", &self.checked_at.map(|t| t.timestamp()))?;
+        link.end()
+    }
+}
 
-    
+impl Serialize for LinkSighting {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        let mut sighting = serializer.serialize_struct("LinkSighting", 3)?;
+        sighting.serialize_field("first_seen", &self.first_seen.timestamp())?;
+        sighting.serialize_field("last_seen", &self.last_seen.timestamp())?;
+        sighting.serialize_field("removed_at", &self.removed_at.map(|t| t.timestamp()))?;
+        sighting.end()
+    }
+}
 
+impl Serialize for ScrapeRun {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::ser::Serializer,
+    {
+        let mut run = serializer.serialize_struct("ScrapeRun", 10)?;
+        run.serialize_field("id", &self.id)?;
+        run.serialize_field("kind", &self.kind)?;
+        run.serialize_field("started_at", &self.started_at.timestamp())?;
+        run.serialize_field("finished_at", &self.finished_at.map(|t| t.timestamp()))?;
+        run.serialize_field(
+            "duration_seconds",
+            &self.finished_at.map(|t| (t - self.started_at).num_seconds()),
+        )?;
+        run.serialize_field("events_found", &self.events_found)?;
+        run.serialize_field("links_found", &self.links_found)?;
+        run.serialize_field("sports", &self.sports())?;
+        run.serialize_field("errors", &self.errors())?;
+        run.serialize_field("success", &self.is_success())?;
+        run.end()
+    }
+}
 
-    #[test]
-    fn test_serialise_streamlink() {
-        let stream = Stream {
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::{EventStatus, FailureKind, ScrapeRun, Stream, StreamWithLinks};
+    use crate::constants::sports::{Catalogue, Sport};
+
+    fn stream(start_time: i64) -> Stream {
+        Stream {
             id: Some(1),
             home: "home".to_string(),
             away: "away".to_string(),
-            start_time: NaiveDateTime::from_timestamp_millis(100000000).unwrap(),
+            start_time: NaiveDateTime::from_timestamp_millis(start_time).unwrap(),
             league: "league".to_string(),
             country: "country".to_string(),
             url: "url".to_string(),
-            stream_link: "stream_link".to_string(),
             sport: "sport".to_string(),
+            home_team_id: Some(2),
+            away_team_id: None,
+            archived_at: None,
+        }
+    }
+
+    #[test]
+    fn test_serialise_streamlink() {
+        let stream = StreamWithLinks {
+            stream: stream(100000000),
+            status: EventStatus::Finished,
+            stream_link: vec!["stream_link".to_string()],
+            links: vec![],
         };
 
         let serialised = serde_json::to_string(&stream).unwrap();
         assert_eq!(serialised,
-                   "{\"id\":1,\"home\":\"home\",\"away\":\"away\",\"start_time\":100000,\"league\":\"league\",\"country\":\"country\",\"url\":\"url\",\"stream_link\":[\"stream_link\"],\"sport\":\"sport\"}");
+                   "{\"id\":1,\"home\":\"home\",\"away\":\"away\",\"start_time\":100000,\"league\":\"league\",\"country\":\"country\",\"url\":\"url\",\"sport\":\"sport\",\"home_team_id\":2,\"away_team_id\":null,\"archived_at\":null,\"status\":\"finished\",\"stream_link\":[\"stream_link\"],\"links\":[]}");
     }
 
     #[test]
     fn test_serialise_streamlink_multiple() {
-        let stream = Stream {
-            id: Some(1),
-            home: "home".to_string(),
-            away: "away".to_string(),
-            start_time: NaiveDateTime::from_timestamp_millis(100000).unwrap(),
-            league: "league".to_string(),
-            country: "country".to_string(),
-            url: "url".to_string(),
-            stream_link: "stream_link,stream_link2".to_string(),
-            sport: "sport".to_string(),
+        let stream = StreamWithLinks {
+            stream: stream(100000),
+            status: EventStatus::Finished,
+            stream_link: vec!["stream_link".to_string(), "https://a.b/?c=d,e".to_string()],
+            links: vec![],
         };
 
         let serialised = serde_json::to_string(&stream).unwrap();
         assert_eq!(serialised,
-                   "{\"id\":1,\"home\":\"home\",\"away\":\"away\",\"start_time\":100,\"league\":\"league\",\"country\":\"country\",\"url\":\"url\",\"stream_link\":[\"stream_link\",\"stream_link2\"],\"sport\":\"sport\"}");
+                   "{\"id\":1,\"home\":\"home\",\"away\":\"away\",\"start_time\":100,\"league\":\"league\",\"country\":\"country\",\"url\":\"url\",\"sport\":\"sport\",\"home_team_id\":2,\"away_team_id\":null,\"archived_at\":null,\"status\":\"finished\",\"stream_link\":[\"stream_link\",\"https://a.b/?c=d,e\"],\"links\":[]}");
     }
 
     #[test]
     fn test_serialise_streamlink_empty() {
-        let stream = Stream {
-            id: Some(1),
-            home: "home".to_string(),
-            away: "away".to_string(),
-            start_time: NaiveDateTime::from_timestamp_millis(90000000).unwrap(),
-            league: "league".to_string(),
-            country: "country".to_string(),
-            url: "url".to_string(),
-            stream_link: "".to_string(),
-            sport: "sport".to_string(),
+        let stream = StreamWithLinks {
+            stream: stream(90000000),
+            status: EventStatus::Finished,
+            stream_link: vec![],
+            links: vec![],
         };
 
         let serialised = serde_json::to_string(&stream).unwrap();
         assert_eq!(serialised,
-                   "{\"id\":1,\"home\":\"home\",\"away\":\"away\",\"start_time\":90000,\"league\":\"league\",\"country\":\"country\",\"url\":\"url\",\"stream_link\":[\"\"],\"sport\":\"sport\"}");
+                   "{\"id\":1,\"home\":\"home\",\"away\":\"away\",\"start_time\":90000,\"league\":\"league\",\"country\":\"country\",\"url\":\"url\",\"sport\":\"sport\",\"home_team_id\":2,\"away_team_id\":null,\"archived_at\":null,\"status\":\"finished\",\"stream_link\":[],\"links\":[]}");
+    }
+
+    #[test]
+    fn test_status_uses_sport_duration() {
+        let start = NaiveDateTime::from_timestamp_opt(1706815800, 0).unwrap();
+        let four_hours_later = start + chrono::Duration::hours(4);
+
+        let mut football = stream(0);
+        football.start_time = start;
+        football.sport = "Football".to_string();
+
+        let mut cricket = football.clone();
+        cricket.sport = "Cricket".to_string();
+
+        let catalogue = Catalogue::default();
+        assert_eq!(football.status(&catalogue, start - chrono::Duration::minutes(1)), EventStatus::Upcoming);
+        assert_eq!(football.status(&catalogue, start), EventStatus::Live);
+        assert_eq!(football.status(&catalogue, four_hours_later), EventStatus::Finished);
+        assert_eq!(cricket.status(&catalogue, four_hours_later), EventStatus::Live);
+
+        // the catalogue in use decides, not the built-in one
+        let short = Catalogue::new(vec![Sport::new("Cricket", "https://cricket.sportshub.stream/").lasting(60)]);
+        assert_eq!(cricket.status(&short, four_hours_later), EventStatus::Finished);
+    }
+
+    #[test]
+    fn test_old_scrape_errors() {
+        let mut run = ScrapeRun {
+            id: 1,
+            kind: "events".to_string(),
+            started_at: NaiveDateTime::from_timestamp_opt(1706815800, 0).unwrap(),
+            finished_at: None,
+            events_found: 0,
+            links_found: 0,
+            sport_counts: "{}".to_string(),
+            errors: r#"["Basketball: no schedule"]"#.to_string(),
+            succeeded: false,
+        };
+
+        let errors = run.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, FailureKind::Unknown);
+        assert_eq!(errors[0].to_string(), "Basketball: no schedule");
+
+        run.errors =
+            r#"[{"kind":"sport","sport":"Basketball","url":null,"attempts":3,"error":"timed out"}]"#.to_string();
+        assert_eq!(run.errors()[0].to_string(), "Basketball: timed out (after 3 attempts)");
     }
 }
@@ -1,5 +1,73 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    links (id) {
+        id -> Nullable<Integer>,
+        event_id -> Integer,
+        url -> Text,
+        position -> Integer,
+        first_seen -> Timestamp,
+        last_seen -> Timestamp,
+        streamer -> Nullable<Text>,
+        language -> Nullable<Text>,
+        quality -> Nullable<Text>,
+        ad_count -> Nullable<Integer>,
+        channel_type -> Nullable<Text>,
+        removed_at -> Nullable<Timestamp>,
+        reachable -> Nullable<Bool>,
+        http_status -> Nullable<Integer>,
+        final_url -> Nullable<Text>,
+        player_type -> Nullable<Text>,
+        checked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    link_sightings (id) {
+        id -> Integer,
+        link_id -> Integer,
+        first_seen -> Timestamp,
+        last_seen -> Timestamp,
+        removed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    mirror_health (sport, url) {
+        sport -> Text,
+        url -> Text,
+        successes -> Integer,
+        failures -> Integer,
+        consecutive_failures -> Integer,
+        last_success -> Nullable<Timestamp>,
+        last_failure -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+        discovered -> Bool,
+    }
+}
+
+diesel::table! {
+    scrape_meta (key) {
+        key -> Text,
+        value -> Text,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    scrape_runs (id) {
+        id -> Integer,
+        kind -> Text,
+        started_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+        events_found -> Integer,
+        links_found -> Integer,
+        sport_counts -> Text,
+        errors -> Text,
+        succeeded -> Bool,
+    }
+}
+
 diesel::table! {
     stream (id) {
         id -> Nullable<Integer>,
@@ -9,7 +77,36 @@ diesel::table! {
         league -> Text,
         country -> Text,
         url -> Text,
-        stream_link -> Text,
         sport -> Text,
+        home_team_id -> Nullable<Integer>,
+        away_team_id -> Nullable<Integer>,
+        archived_at -> Nullable<Timestamp>,
     }
 }
+
+diesel::table! {
+    team_aliases (alias) {
+        alias -> Text,
+        team_id -> Integer,
+    }
+}
+
+diesel::table! {
+    teams (id) {
+        id -> Integer,
+        name -> Text,
+    }
+}
+
+diesel::joinable!(team_aliases -> teams (team_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    link_sightings,
+    links,
+    mirror_health,
+    scrape_meta,
+    scrape_runs,
+    stream,
+    team_aliases,
+    teams,
+);
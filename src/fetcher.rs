@@ -0,0 +1,262 @@
+//! Backends used to download the html that we scrape.
+//!
+//! The scraper only ever needs the html of a single element (like `#sports-shedule`),
+//! so every backend returns that and the parsing is done the same way afterwards.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Error;
+use chrono::{FixedOffset, Offset, Utc};
+use headless_chrome::{protocol::cdp::Emulation, Browser, Tab};
+
+use crate::query_selectors::DomParseError;
+
+/// user agent sent by the http backend, some mirrors refuse requests without one
+pub const USER_AGENT: &str =
+    "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+/// The cookie the site renders the times in, its javascript sets it from the timezone of the browser.
+/// Without it the times follow the geolocation of the ip
+pub const TIMEZONE_COOKIE: &str = "timezone";
+
+/// A way of getting the html of a page
+pub trait Fetcher {
+    /// Navigate to `url` and return the html of the whole page
+    ///
+    /// # Arguments
+    /// * `url` - The page to fetch
+    fn fetch_page(&self, url: &str) -> Result<String, Error>;
+
+    /// Navigate to `url` and return the outer html of the first element matching `selector`
+    ///
+    /// # Arguments
+    /// * `url` - The page to fetch
+    /// * `selector` - A css selector (`#id` or `.class`) of the element we want
+    fn fetch_element(&self, url: &str, selector: &str) -> Result<String, Error> {
+        select_element(&self.fetch_page(url)?, selector)
+    }
+
+    /// The utc offset the times on the fetched pages are shown in
+    fn utc_offset(&self) -> Result<FixedOffset, Error> {
+        Ok(Utc.fix())
+    }
+}
+
+/// Get the outer html of the first element matching `selector` from the html of a page
+///
+/// # Arguments
+/// * `html` - The html of the whole page
+/// * `selector` - A css selector (`#id` or `.class`) of the element we want
+///
+/// # Examples
+/// ```
+/// use scraper::fetcher::select_element;
+///
+/// let html = r#"<html><body><div id="sports-shedule"><span>games</span></div></body></html>"#;
+///
+/// let schedule = select_element(html, "#sports-shedule").unwrap();
+/// assert_eq!(schedule, r#"<div id="sports-shedule"><span>games</span></div>"#);
+/// ```
+pub fn select_element(html: &str, selector: &str) -> Result<String, Error> {
+    let dom = tl::parse(html, tl::ParserOptions::default())?;
+    let parser = dom.parser();
+
+    let element = dom
+        .query_selector(selector)
+        .ok_or(DomParseError::NotFound)?
+        .next()
+        .ok_or(DomParseError::NotFound)?
+        .get(parser)
+        .ok_or(DomParseError::Unknown)?
+        .outer_html(parser)
+        .to_string();
+
+    Ok(element)
+}
+
+/// Fetches pages with plain http requests, no browser needed.
+/// The site renders the times server side, every request sends the timezone cookie so they are
+/// in utc, or in the offset given with [`HttpFetcher::with_utc_offset`], wherever the scraper runs
+///
+/// # Examples
+/// ```no_run
+/// use scraper::fetcher::{Fetcher, HttpFetcher};
+///
+/// let fetcher = HttpFetcher::new();
+/// let html = fetcher
+///     .fetch_element("https://reddit3.sportshub.stream/", "#sports-shedule")
+///     .unwrap();
+/// ```
+pub struct HttpFetcher {
+    agent: ureq::Agent,
+    offset: FixedOffset,
+}
+
+impl HttpFetcher {
+    pub fn new() -> HttpFetcher {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .user_agent(USER_AGENT)
+            .build();
+
+        HttpFetcher {
+            agent,
+            offset: Utc.fix(),
+        }
+    }
+
+    /// Ask for the times of the pages in `offset` instead of utc
+    pub fn with_utc_offset(mut self, offset: FixedOffset) -> HttpFetcher {
+        self.offset = offset;
+        self
+    }
+}
+
+impl Default for HttpFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fetcher for HttpFetcher {
+    fn fetch_page(&self, url: &str) -> Result<String, Error> {
+        let cookie = timezone_cookie(self.offset)?;
+
+        Ok(self.agent.get(url).set("Cookie", &cookie).call()?.into_string()?)
+    }
+
+    fn utc_offset(&self) -> Result<FixedOffset, Error> {
+        Ok(self.offset)
+    }
+}
+
+/// Fetches pages with a headless_chrome tab, for when the http backend gets blocked
+/// or the page needs javascript to render.
+/// The site converts the times to the timezone of the browser, so we force it on the tab
+pub struct ChromeFetcher {
+    // we keep the browser alive for as long as the tab is used
+    browser: Option<Browser>,
+    tab: Arc<Tab>,
+}
+
+impl ChromeFetcher {
+    /// Launch a new browser and open a tab in it
+    ///
+    /// # Arguments
+    /// * `headless` - Whether to run the browser in headless mode
+    /// * `offset` - The utc offset the tab shows the times in
+    pub fn new(headless: bool, offset: FixedOffset) -> Result<ChromeFetcher, Error> {
+        // realised we didnt need adblocker when headless
+        let browser = Browser::new({
+            headless_chrome::LaunchOptions {
+                headless,
+                sandbox: true,
+                ignore_certificate_errors: true,
+                ..Default::default()
+            }
+        })?;
+
+        let tab = browser.new_tab()?;
+        force_timezone(&tab, offset)?;
+
+        Ok(ChromeFetcher {
+            browser: Some(browser),
+            tab,
+        })
+    }
+
+    /// Use a tab from an already running browser
+    ///
+    /// # Arguments
+    /// * `tab` - The tab to navigate with
+    /// * `offset` - The utc offset the tab shows the times in
+    pub fn from_tab(tab: Arc<Tab>, offset: FixedOffset) -> Result<ChromeFetcher, Error> {
+        force_timezone(&tab, offset)?;
+
+        Ok(ChromeFetcher { browser: None, tab })
+    }
+}
+
+/// The IANA name of a fixed offset, chrome only knows names.
+/// The `Etc/GMT` zones have their sign inverted, `Etc/GMT-1` is `+01:00`.
+/// They don't have daylight saving time, so the pages stay in the same offset all year
+///
+/// # Example
+/// ```
+/// use chrono::FixedOffset;
+/// use scraper::fetcher::timezone_name;
+///
+/// assert_eq!(timezone_name(FixedOffset::east_opt(3600).unwrap()).unwrap(), "Etc/GMT-1");
+/// assert_eq!(timezone_name(FixedOffset::west_opt(5 * 3600).unwrap()).unwrap(), "Etc/GMT+5");
+/// assert!(timezone_name(FixedOffset::east_opt(5 * 3600 + 1800).unwrap()).is_err());
+/// ```
+pub fn timezone_name(offset: FixedOffset) -> Result<String, Error> {
+    let seconds = offset.local_minus_utc();
+
+    if seconds % 3600 != 0 {
+        return Err(anyhow::anyhow!("chrome can only use whole hour offsets, not {}", offset));
+    }
+
+    Ok(match seconds / 3600 {
+        0 => "UTC".to_string(),
+        hours if hours > 0 => format!("Etc/GMT-{}", hours),
+        hours => format!("Etc/GMT+{}", -hours),
+    })
+}
+
+/// The cookie that makes the site render the times in `offset`, see [`TIMEZONE_COOKIE`]
+///
+/// # Example
+/// ```
+/// use chrono::FixedOffset;
+/// use scraper::fetcher::timezone_cookie;
+///
+/// assert_eq!(timezone_cookie(FixedOffset::east_opt(0).unwrap()).unwrap(), "timezone=UTC");
+/// assert_eq!(timezone_cookie(FixedOffset::east_opt(3600).unwrap()).unwrap(), "timezone=Etc%2FGMT-1");
+/// ```
+pub fn timezone_cookie(offset: FixedOffset) -> Result<String, Error> {
+    Ok(format!("{}={}", TIMEZONE_COOKIE, urlencoding::encode(&timezone_name(offset)?)))
+}
+
+/// Make the tab render dates in `offset`, whatever the timezone of the machine is
+fn force_timezone(tab: &Tab, offset: FixedOffset) -> Result<(), Error> {
+    tab.call_method(Emulation::SetTimezoneOverride {
+        timezone_id: timezone_name(offset)?,
+    })?;
+
+    Ok(())
+}
+
+impl Fetcher for ChromeFetcher {
+    fn fetch_page(&self, url: &str) -> Result<String, Error> {
+        self.tab.navigate_to(url)?.wait_until_navigated()?.get_content()
+    }
+
+    fn fetch_element(&self, url: &str, selector: &str) -> Result<String, Error> {
+        // we navigate to the page and wait until the element is loaded
+        self.tab.navigate_to(url)?.wait_for_element(selector)?;
+
+        self.tab.find_element(selector)?.get_content()
+    }
+
+    fn utc_offset(&self) -> Result<FixedOffset, Error> {
+        // read the override back from javascript, which is what converts the times on the page
+        let minutes = self
+            .tab
+            .evaluate("-new Date().getTimezoneOffset()", false)?
+            .value
+            .and_then(|v| v.as_i64())
+            .ok_or(anyhow::anyhow!("could not read the timezone of the tab"))?;
+
+        FixedOffset::east_opt(minutes as i32 * 60).ok_or(anyhow::anyhow!("invalid utc offset {} minutes", minutes))
+    }
+}
+
+impl Drop for ChromeFetcher {
+    fn drop(&mut self) {
+        // we close the tab because otherwise it shows an error when program finishes
+        if self.browser.is_some() {
+            let _ = self.tab.close(true);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! This module contains the GraphQL schema of the API, using juniper.
+//! The resolvers use the same queries as the rest api, from `db::helpers`.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use juniper::{graphql_object, EmptyMutation, EmptySubscription, FieldResult, RootNode};
+
+use crate::{
+    constants::sports::{Catalogue, Sport},
+    db::{
+        helpers::{self, page_size, DbPool, LeagueWithCountry, SortOrder, StreamFilter},
+        models::{Link, StreamWithLinks},
+    },
+};
+
+/// What every resolver has access to
+pub struct Context {
+    pub pool: DbPool,
+    pub catalogue: Arc<Catalogue>,
+}
+
+impl juniper::Context for Context {}
+
+pub type Schema = RootNode<'static, Query, EmptyMutation<Context>, EmptySubscription<Context>>;
+
+pub fn schema() -> Schema {
+    Schema::new(Query, EmptyMutation::new(), EmptySubscription::new())
+}
+
+pub struct Query;
+
+#[graphql_object(context = Context)]
+impl Query {
+    /// Events matching all the given filters, ordered by start time.
+    /// At most `limit` of them, 50 by default and 500 at most like `/streams`
+    #[allow(clippy::too_many_arguments)]
+    fn streams(
+        context: &Context,
+        sport: Option<String>,
+        league: Option<String>,
+        country: Option<String>,
+        team: Option<String>,
+        starts_after: Option<DateTime<Utc>>,
+        starts_before: Option<DateTime<Utc>>,
+        has_links: Option<bool>,
+        descending: Option<bool>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> FieldResult<Vec<StreamWithLinks>> {
+        let limit = page_size(limit.map(i64::from), offset.map(i64::from))?;
+
+        let filter = StreamFilter {
+            sport,
+            league,
+            country,
+            team,
+            starts_after: starts_after.map(|t| t.naive_utc()),
+            starts_before: starts_before.map(|t| t.naive_utc()),
+            has_links,
+            order: if descending.unwrap_or(false) {
+                SortOrder::Descending
+            } else {
+                SortOrder::Ascending
+            },
+            limit: Some(limit),
+            offset: offset.map(i64::from),
+            cursor: None,
+        };
+
+        let mut conn = context.pool.get()?;
+        let streams = helpers::get_filtered_streams(&mut conn, &filter)?;
+
+        Ok(helpers::attach_links(&mut conn, &context.catalogue, streams)?)
+    }
+
+    /// A single event
+    fn stream(context: &Context, id: i32) -> FieldResult<Option<StreamWithLinks>> {
+        let mut conn = context.pool.get()?;
+        let streams = helpers::get_streams_by_id(&mut conn, id)?;
+
+        Ok(helpers::attach_links(&mut conn, &context.catalogue, streams)?.into_iter().next())
+    }
+
+    /// Every league we have events for, with its country
+    fn leagues(context: &Context) -> FieldResult<Vec<LeagueWithCountry>> {
+        let mut conn = context.pool.get()?;
+
+        Ok(helpers::get_unique_leagues_with_country(&mut conn)?)
+    }
+
+    /// Every sport we scrape, from the catalogue in use
+    fn sports(context: &Context) -> Vec<Sport> {
+        context.catalogue.enabled()
+    }
+}
+
+#[graphql_object(name = "Stream", context = Context)]
+impl StreamWithLinks {
+    fn id(&self) -> Option<i32> {
+        self.stream.id
+    }
+
+    fn home(&self) -> &str {
+        &self.stream.home
+    }
+
+    fn away(&self) -> &str {
+        &self.stream.away
+    }
+
+    /// The same team always has the same id, whatever its spelling on the page
+    fn home_team_id(&self) -> Option<i32> {
+        self.stream.home_team_id
+    }
+
+    fn away_team_id(&self) -> Option<i32> {
+        self.stream.away_team_id
+    }
+
+    fn start_time(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.stream.start_time, Utc)
+    }
+
+    fn league(&self) -> &str {
+        &self.stream.league
+    }
+
+    fn country(&self) -> &str {
+        &self.stream.country
+    }
+
+    /// The event page on sportshub
+    fn url(&self) -> &str {
+        &self.stream.url
+    }
+
+    fn sport(&self) -> &str {
+        &self.stream.sport
+    }
+
+    /// `upcoming`, `live` or `finished`, from the start time and how long the sport usually lasts
+    fn status(&self) -> &str {
+        self.status.as_str()
+    }
+
+    fn links(&self) -> &[Link] {
+        &self.links
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Link {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn streamer(&self) -> Option<&str> {
+        self.streamer.as_deref()
+    }
+
+    fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    fn quality(&self) -> Option<&str> {
+        self.quality.as_deref()
+    }
+
+    fn ad_count(&self) -> Option<i32> {
+        self.ad_count
+    }
+
+    fn channel_type(&self) -> Option<&str> {
+        self.channel_type.as_deref()
+    }
+
+    fn first_seen(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.first_seen, Utc)
+    }
+
+    fn last_seen(&self) -> DateTime<Utc> {
+        DateTime::from_naive_utc_and_offset(self.last_seen, Utc)
+    }
+
+    /// Whether the link worked the last time it was checked, null if it never was
+    fn reachable(&self) -> Option<bool> {
+        self.reachable
+    }
+
+    /// `hls`, `dash`, `video`, `iframe`, `acestream` or `unknown`
+    fn player_type(&self) -> Option<&str> {
+        self.player_type.as_deref()
+    }
+
+    fn checked_at(&self) -> Option<DateTime<Utc>> {
+        self.checked_at.map(|t| DateTime::from_naive_utc_and_offset(t, Utc))
+    }
+}
+
+#[graphql_object(name = "League", context = Context)]
+impl LeagueWithCountry {
+    fn league(&self) -> &str {
+        &self.league
+    }
+
+    fn country(&self) -> &str {
+        &self.country
+    }
+}
+
+#[graphql_object(context = Context)]
+impl Sport {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The page we scrape the events of this sport from
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// How long an event usually lasts, in minutes
+    fn duration(&self) -> i32 {
+        self.duration as i32
+    }
+}
@@ -1,12 +1,16 @@
+pub mod config;
 pub mod constants;
 pub mod date_parser;
 pub mod db;
+pub mod fetcher;
+pub mod graphql;
+pub mod link_checker;
+pub mod mirrors;
 pub mod query_selectors;
+pub mod retry;
+pub mod scheduler;
 pub mod scrape;
 pub mod scrape_utils;
+pub mod search;
+pub mod teams;
 pub mod web_server_routes;
-
-#[macro_use]
-extern crate diesel;
-#[macro_use]
-extern crate rocket;
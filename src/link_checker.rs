@@ -0,0 +1,406 @@
+//! Checks that the stream links we scraped still work.
+//!
+//! Every link is opened (a `HEAD` then a `GET` with plain http, or a chrome tab for embed pages that
+//! need javascript) by a bounded number of workers, and we save whether it answered, its status,
+//! where the redirects ended up and what kind of player is behind it.
+//! The api leaves out the links that were found dead.
+
+use std::{
+    collections::VecDeque,
+    io::Read,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Error;
+use diesel::SqliteConnection;
+use headless_chrome::Browser;
+use serde::Serialize;
+
+use crate::{
+    db::{self, models::LinkCheck},
+    fetcher::USER_AGENT,
+    scrape::panic_message,
+    scrape_utils::create_browser,
+};
+
+/// How long a link has to answer before it is considered dead
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many links are checked at the same time by default
+pub const DEFAULT_WORKERS: usize = 10;
+
+/// How much of a page we read to find the player, embed pages are small
+const MAX_BODY_BYTES: u64 = 512 * 1024;
+
+/// The kind of player a link leads to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayerType {
+    /// An `.m3u8` playlist
+    Hls,
+    /// An `.mpd` manifest
+    Dash,
+    /// A page with a `<video>` tag, or a video file
+    Video,
+    /// A page embedding the player of another site
+    Iframe,
+    /// An `acestream://` link, it needs the acestream player
+    Acestream,
+    /// We couldn't tell
+    Unknown,
+}
+
+impl PlayerType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlayerType::Hls => "hls",
+            PlayerType::Dash => "dash",
+            PlayerType::Video => "video",
+            PlayerType::Iframe => "iframe",
+            PlayerType::Acestream => "acestream",
+            PlayerType::Unknown => "unknown",
+        }
+    }
+}
+
+/// Guess the player behind a link from where it ended up, its content type and the start of its body
+///
+/// # Arguments
+/// * `url` - The url after the redirects
+/// * `content_type` - The content type of the response, without the charset
+/// * `body` - The start of the body, empty if we only did a `HEAD`
+///
+/// # Example
+/// ```
+/// use scraper::link_checker::{detect_player, PlayerType};
+///
+/// assert_eq!(detect_player("https://cdn.example.com/live.m3u8", "", ""), PlayerType::Hls);
+/// assert_eq!(
+///     detect_player("https://example.com/embed/1", "text/html", r#"<iframe src="https://player.example.com">"#),
+///     PlayerType::Iframe
+/// );
+/// ```
+pub fn detect_player(url: &str, content_type: &str, body: &str) -> PlayerType {
+    let url = url.to_lowercase();
+    let content_type = content_type.to_lowercase();
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+
+    if url.starts_with("acestream://") {
+        PlayerType::Acestream
+    } else if path.ends_with(".m3u8") || content_type.contains("mpegurl") {
+        PlayerType::Hls
+    } else if path.ends_with(".mpd") || content_type == "application/dash+xml" {
+        PlayerType::Dash
+    } else if content_type.starts_with("video/") {
+        PlayerType::Video
+    } else {
+        let body = body.to_lowercase();
+
+        // a page can have both, the video tag is the player we want
+        if body.contains("<video") || body.contains(".m3u8") {
+            PlayerType::Video
+        } else if body.contains("<iframe") {
+            PlayerType::Iframe
+        } else {
+            PlayerType::Unknown
+        }
+    }
+}
+
+/// A way of opening a link to see if it works
+pub trait Prober: Sync {
+    /// Open `url` and tell what we found, failing to connect is a dead link and not an error
+    ///
+    /// # Arguments
+    /// * `url` - The stream link
+    fn probe(&self, url: &str) -> LinkCheck;
+}
+
+/// The check of a link we can't open, like `acestream://` ones, we only know its player
+fn unchecked(url: &str) -> LinkCheck {
+    LinkCheck {
+        reachable: None,
+        player_type: Some(detect_player(url, "", "").as_str().to_owned()),
+        ..Default::default()
+    }
+}
+
+fn is_http(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Checks links with plain http requests, a `HEAD` first and a `GET` when we need the body to find the player
+/// or the server doesn't like `HEAD` requests
+///
+/// # Example
+/// ```no_run
+/// use scraper::link_checker::{HttpProber, Prober, DEFAULT_TIMEOUT};
+///
+/// let check = HttpProber::new(DEFAULT_TIMEOUT).probe("https://example.com/embed/1");
+/// println!("{:?}", check.reachable);
+/// ```
+pub struct HttpProber {
+    agent: ureq::Agent,
+}
+
+impl HttpProber {
+    /// # Arguments
+    /// * `timeout` - How long a link has to answer
+    pub fn new(timeout: Duration) -> HttpProber {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(timeout)
+            .user_agent(USER_AGENT)
+            .build();
+
+        HttpProber { agent }
+    }
+
+    fn check_response(response: ureq::Response, read_body: bool) -> LinkCheck {
+        let status = response.status();
+        let final_url = response.get_url().to_owned();
+        let content_type = response.content_type().to_owned();
+
+        let mut body = String::new();
+        if read_body {
+            // a body that isn't text just doesn't tell us anything
+            let _ = response.into_reader().take(MAX_BODY_BYTES).read_to_string(&mut body);
+        }
+
+        LinkCheck {
+            reachable: Some(true),
+            http_status: Some(status as i32),
+            player_type: Some(detect_player(&final_url, &content_type, &body).as_str().to_owned()),
+            final_url: Some(final_url),
+        }
+    }
+
+    fn check_error(error: ureq::Error) -> LinkCheck {
+        match error {
+            ureq::Error::Status(status, response) => LinkCheck {
+                reachable: Some(false),
+                http_status: Some(status as i32),
+                final_url: Some(response.get_url().to_owned()),
+                player_type: None,
+            },
+            ureq::Error::Transport(_) => LinkCheck {
+                reachable: Some(false),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl Prober for HttpProber {
+    fn probe(&self, url: &str) -> LinkCheck {
+        if !is_http(url) {
+            return unchecked(url);
+        }
+
+        // the head is enough for streams and videos, pages need a get to find the player in them
+        if let Ok(response) = self.agent.head(url).call() {
+            if !response.content_type().contains("html") {
+                return HttpProber::check_response(response, false);
+            }
+        }
+
+        match self.agent.get(url).call() {
+            Ok(response) => HttpProber::check_response(response, true),
+            Err(e) => HttpProber::check_error(e),
+        }
+    }
+}
+
+/// Checks links in chrome tabs, for embed pages that only build their player with javascript
+pub struct ChromeProber {
+    browser: Browser,
+    timeout: Duration,
+}
+
+impl ChromeProber {
+    /// Launch a browser, every check opens its own tab in it
+    ///
+    /// # Arguments
+    /// * `headless` - Whether to run the browser in headless mode
+    /// * `timeout` - How long a page has to load
+    pub fn new(headless: bool, timeout: Duration) -> Result<ChromeProber, Error> {
+        Ok(ChromeProber {
+            browser: create_browser(headless)?,
+            timeout,
+        })
+    }
+
+    fn open(&self, url: &str) -> Result<LinkCheck, Error> {
+        let tab = self.browser.new_tab()?;
+        tab.set_default_timeout(self.timeout);
+
+        let loaded = tab.navigate_to(url).and_then(|t| t.wait_until_navigated());
+
+        let check = match loaded {
+            Ok(_) => {
+                let final_url = tab.get_url();
+                let html = tab.get_content()?;
+
+                LinkCheck {
+                    reachable: Some(true),
+                    http_status: None,
+                    player_type: Some(detect_player(&final_url, "", &html).as_str().to_owned()),
+                    final_url: Some(final_url),
+                }
+            }
+            Err(_) => LinkCheck {
+                reachable: Some(false),
+                ..Default::default()
+            },
+        };
+
+        tab.close(true)?;
+
+        Ok(check)
+    }
+}
+
+impl Prober for ChromeProber {
+    fn probe(&self, url: &str) -> LinkCheck {
+        if !is_http(url) {
+            return unchecked(url);
+        }
+
+        // the browser itself failing doesn't tell us anything about the link
+        self.open(url).unwrap_or_else(|_| unchecked(url))
+    }
+}
+
+/// What [`check_links`] found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CheckSummary {
+    pub checked: usize,
+    pub reachable: usize,
+    pub dead: usize,
+    /// Links we can't open, like `acestream://` ones
+    pub unchecked: usize,
+    /// Links whose check panicked, nothing is saved for them so they are checked again next time
+    pub failed: usize,
+}
+
+/// Check the links on the pages of the current events that weren't checked since `checked_before`,
+/// with `workers` links opened at the same time
+///
+/// # Arguments
+/// * `conn` - The database, the results are saved as they come in
+/// * `prober` - How to open the links
+/// * `workers` - How many links are checked at the same time
+/// * `checked_before` - Links checked after this are skipped
+/// * `now` - Saved as the time the links were checked
+pub fn check_links(
+    conn: &mut SqliteConnection,
+    prober: &dyn Prober,
+    workers: usize,
+    checked_before: chrono::NaiveDateTime,
+    now: chrono::NaiveDateTime,
+) -> Result<CheckSummary, Error> {
+    let queue: VecDeque<(i32, String)> = db::helpers::get_links_to_check(conn, checked_before)?
+        .into_iter()
+        .filter_map(|l| l.id.map(|id| (id, l.url)))
+        .collect();
+
+    let mut summary = CheckSummary::default();
+
+    if queue.is_empty() {
+        return Ok(summary);
+    }
+
+    let workers = workers.clamp(1, queue.len());
+    let queue = Mutex::new(queue);
+    let (sender, receiver) = mpsc::channel();
+
+    // the workers only open links, the results are saved here as they come in
+    // so a run that is stopped halfway keeps what it checked
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let queue = &queue;
+
+            scope.spawn(move || loop {
+                let Some((id, url)) = queue.lock().unwrap().pop_front() else {
+                    break;
+                };
+
+                // a panic on one odd link shouldn't take the worker and the whole run down with it
+                let check = panic::catch_unwind(AssertUnwindSafe(|| prober.probe(&url))).map_err(panic_message);
+
+                // nobody is listening anymore, saving failed
+                if sender.send((id, url, check)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        // the workers hold the other senders, the loop ends once they are all done
+        drop(sender);
+
+        for (id, url, check) in receiver {
+            let check = match check {
+                Ok(check) => check,
+                Err(e) => {
+                    eprintln!("Checking {} panicked: {}", url, e);
+                    summary.failed += 1;
+                    continue;
+                }
+            };
+
+            if let Err(e) = db::helpers::save_link_check(conn, id, &check, now) {
+                // the workers stop after the links they are on
+                queue.lock().unwrap().clear();
+                return Err(e);
+            }
+
+            summary.checked += 1;
+            match check.reachable {
+                Some(true) => summary.reachable += 1,
+                Some(false) => summary.dead += 1,
+                None => summary.unchecked += 1,
+            }
+        }
+
+        Ok(())
+    })?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_detect_from_url() {
+        assert_eq!(detect_player("https://cdn.example.com/live/index.m3u8?token=1", "", ""), PlayerType::Hls);
+        assert_eq!(detect_player("https://cdn.example.com/live.mpd", "", ""), PlayerType::Dash);
+        assert_eq!(detect_player("acestream://3f8a1c2b9d", "", ""), PlayerType::Acestream);
+    }
+
+    #[test]
+    fn test_detect_from_content() {
+        assert_eq!(
+            detect_player("https://example.com/1", "application/vnd.apple.mpegurl", ""),
+            PlayerType::Hls
+        );
+        assert_eq!(detect_player("https://example.com/1", "video/mp4", ""), PlayerType::Video);
+        assert_eq!(
+            detect_player("https://example.com/1", "text/html", "<body><VIDEO src=x></video></body>"),
+            PlayerType::Video
+        );
+        assert_eq!(detect_player("https://example.com/1", "text/html", "<p>nothing</p>"), PlayerType::Unknown);
+    }
+
+    #[test]
+    fn test_non_http_links_are_not_checked() {
+        let check = HttpProber::new(DEFAULT_TIMEOUT).probe("acestream://3f8a1c2b9d");
+
+        assert_eq!(check.reachable, None);
+        assert_eq!(check.player_type.as_deref(), Some("acestream"));
+    }
+}
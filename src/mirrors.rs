@@ -0,0 +1,269 @@
+//! Sportshub mirrors rotate, so the page of a sport can be at a few urls.
+//!
+//! The mirrors of a sport are tried one after the other until one shows what we want, starting with the
+//! last one that worked. How every mirror did is saved in `mirror_health`, a mirror that keeps failing
+//! is only tried once the others failed too. The current mirrors can also be found on a landing page
+//! linking to them, see [`discover`].
+
+use anyhow::{anyhow, Error};
+use chrono::{Duration, NaiveDateTime};
+use diesel::SqliteConnection;
+
+use crate::{
+    constants::sports::Sport,
+    db::{self, models::MirrorHealth},
+    fetcher::Fetcher,
+    retry::{GaveUp, RetryPolicy},
+};
+
+/// A mirror that failed this many times in a row is tried last...
+pub const MAX_CONSECUTIVE_FAILURES: i32 = 3;
+
+/// ...until it rested this long
+pub const COOLDOWN_MINUTES: i64 = 60;
+
+/// Whether `mirror` failed too often lately to be tried before the others
+pub fn is_resting(mirror: &MirrorHealth, now: NaiveDateTime) -> bool {
+    mirror.consecutive_failures >= MAX_CONSECUTIVE_FAILURES
+        && mirror
+            .last_failure
+            .is_some_and(|t| now - t < Duration::minutes(COOLDOWN_MINUTES))
+}
+
+/// The urls to try for `sport`, in order: the last one that worked, the ones found on the landing page,
+/// then the configured ones. The mirrors that keep failing go last.
+///
+/// # Arguments
+/// * `sport` - The sport, with its configured urls
+/// * `working` - The last mirror that worked, see [`db::helpers::get_working_mirror`]
+/// * `health` - How the mirrors of the sport did, see [`db::helpers::get_mirror_health`]
+/// * `now` - The current time
+pub fn order_mirrors(
+    sport: &Sport,
+    working: Option<&str>,
+    health: &[MirrorHealth],
+    now: NaiveDateTime,
+) -> Vec<String> {
+    let discovered = health.iter().filter(|m| m.discovered).map(|m| m.url.as_str());
+
+    let mut urls: Vec<String> = vec![];
+    for url in working.into_iter().chain(discovered).chain(sport.urls()) {
+        if !urls.iter().any(|u| u == url) {
+            urls.push(url.to_owned());
+        }
+    }
+
+    let resting = |url: &String| health.iter().any(|m| m.url == *url && is_resting(m, now));
+
+    // the sort is stable, the mirrors keep their order within each group
+    urls.sort_by_key(resting);
+
+    urls
+}
+
+/// Get the outer html of `selector` from the first mirror of `sport` that shows it,
+/// every mirror is tried `retry.attempts` times before moving on to the next one
+///
+/// # Arguments
+/// * `fetcher` - How to download the pages
+/// * `conn` - Where the health of the mirrors is saved
+/// * `sport` - The sport, with its configured urls
+/// * `selector` - The element we want, a mirror that doesn't show it failed
+/// * `retry` - How often a mirror is tried
+/// * `now` - The current time
+///
+/// # Returns
+/// A [`GaveUp`] with the attempts on every mirror once none of them worked
+pub fn fetch_from_mirrors(
+    fetcher: &dyn Fetcher,
+    conn: &mut SqliteConnection,
+    sport: &Sport,
+    selector: &str,
+    retry: &RetryPolicy,
+    now: NaiveDateTime,
+) -> Result<String, Error> {
+    let working = db::helpers::get_working_mirror(conn, &sport.name)?;
+    let health = db::helpers::get_mirror_health(conn, Some(&sport.name))?;
+
+    let mut attempts = 0;
+    let mut failures = vec![];
+
+    for url in order_mirrors(sport, working.as_deref(), &health, now) {
+        match retry.run(|_| fetcher.fetch_element(&url, selector)) {
+            Ok(html) => {
+                db::helpers::record_mirror_success(conn, &sport.name, &url, now)?;
+                return Ok(html);
+            }
+            Err(gave_up) => {
+                attempts += gave_up.attempts;
+                db::helpers::record_mirror_failure(conn, &sport.name, &url, &gave_up.error.to_string(), now)?;
+                failures.push(format!("{}: {}", url, gave_up.error));
+            }
+        }
+    }
+
+    // a single mirror reads like it always did
+    let error = match failures.len() {
+        1 => anyhow!(failures.remove(0)),
+        _ => anyhow!("no mirror worked, {}", failures.join(", ")),
+    };
+
+    Err(GaveUp { attempts, error }.into())
+}
+
+/// What a url has in common with the other mirrors of the same page: the first and second to last labels
+/// of the host without their trailing digits, and the path.
+/// `reddit3.sportshub.stream` and `reddit4.sportshub.fan` are the same page.
+fn mirror_key(url: &str) -> Option<(String, String, String)> {
+    let (_, rest) = url.split_once("://")?;
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+
+    let host = host.split(':').next()?.to_lowercase();
+    let labels: Vec<&str> = host.split('.').collect();
+
+    if labels.len() < 2 {
+        return None;
+    }
+
+    let without_digits = |label: &str| label.trim_end_matches(|c: char| c.is_ascii_digit()).to_owned();
+
+    Some((
+        without_digits(labels[0]),
+        without_digits(labels[labels.len() - 2]),
+        path.trim_end_matches('/').to_lowercase(),
+    ))
+}
+
+/// The links in `links` that are new mirrors of one of `sports`
+///
+/// # Returns
+/// The name of the sport and the url of every new mirror
+///
+/// # Example
+/// ```
+/// use scraper::{constants::sports, mirrors::match_mirrors};
+///
+/// let links = vec![
+///     "https://reddit4.sportshub.stream/".to_string(),
+///     "https://reddit3.sportshub.stream/".to_string(),
+///     "https://www.reddit.com/".to_string(),
+/// ];
+///
+/// let found = match_mirrors(&links, &[sports::SOCCER]);
+/// assert_eq!(found, vec![("Football".to_string(), "https://reddit4.sportshub.stream/".to_string())]);
+/// ```
+pub fn match_mirrors(links: &[String], sports: &[Sport]) -> Vec<(String, String)> {
+    let mut found: Vec<(String, String)> = vec![];
+
+    for sport in sports {
+        let known = sport.urls();
+        let keys: Vec<_> = known.iter().filter_map(|u| mirror_key(u)).collect();
+
+        for link in links {
+            let is_new = !known.contains(&link.as_str()) && !found.iter().any(|(_, u)| u == link);
+
+            if is_new && mirror_key(link).is_some_and(|k| keys.contains(&k)) {
+                found.push((sport.name.to_string(), link.clone()));
+            }
+        }
+    }
+
+    found
+}
+
+/// Find the current mirrors of `sports` on a page linking to them
+///
+/// # Arguments
+/// * `fetcher` - How to download the page
+/// * `landing_page` - The page with the links, like `https://sportshub.stream/`
+/// * `sports` - The sports to find mirrors of
+///
+/// # Returns
+/// The name of the sport and the url of every mirror that isn't configured yet
+pub fn discover(fetcher: &dyn Fetcher, landing_page: &str, sports: &[Sport]) -> Result<Vec<(String, String)>, Error> {
+    let html = fetcher.fetch_page(landing_page)?;
+
+    let dom = tl::parse(&html, tl::ParserOptions::default())?;
+    let parser = dom.parser();
+
+    let links: Vec<String> = dom
+        .query_selector("a[href]")
+        .into_iter()
+        .flatten()
+        .filter_map(|a| {
+            let href = a.get(parser)?.as_tag()?.attributes().get("href")??;
+            Some(href.as_utf8_str().trim().to_owned())
+        })
+        .filter(|href| href.starts_with("http://") || href.starts_with("https://"))
+        .collect();
+
+    Ok(match_mirrors(&links, sports))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants::sports;
+
+    fn at(minutes: i64) -> NaiveDateTime {
+        NaiveDateTime::from_timestamp_opt(1706702400, 0).unwrap() + Duration::minutes(minutes)
+    }
+
+    fn health(url: &str, consecutive_failures: i32, last_failure: i64, discovered: bool) -> MirrorHealth {
+        MirrorHealth {
+            sport: "Football".to_string(),
+            url: url.to_string(),
+            successes: 0,
+            failures: consecutive_failures,
+            consecutive_failures,
+            last_success: None,
+            last_failure: Some(at(last_failure)),
+            last_error: None,
+            discovered,
+        }
+    }
+
+    #[test]
+    fn test_working_mirror_first() {
+        let mut soccer = sports::SOCCER.clone();
+        soccer.mirrors = vec!["https://reddit4.sportshub.stream/".to_string()];
+
+        let found = vec![health("https://reddit5.sportshub.stream/", 0, 0, true)];
+        let order = order_mirrors(&soccer, Some("https://reddit4.sportshub.stream/"), &found, at(0));
+
+        assert_eq!(
+            order,
+            vec![
+                "https://reddit4.sportshub.stream/",
+                "https://reddit5.sportshub.stream/",
+                "https://reddit3.sportshub.stream/",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failing_mirror_last() {
+        let mut soccer = sports::SOCCER.clone();
+        soccer.mirrors = vec!["https://reddit4.sportshub.stream/".to_string()];
+
+        let dead = vec![health("https://reddit3.sportshub.stream/", 3, 0, false)];
+        assert_eq!(order_mirrors(&soccer, None, &dead, at(10))[0], "https://reddit4.sportshub.stream/");
+
+        // it gets another chance once it rested
+        assert_eq!(order_mirrors(&soccer, None, &dead, at(60))[0], "https://reddit3.sportshub.stream/");
+    }
+
+    #[test]
+    fn test_mirror_key() {
+        assert_eq!(mirror_key("https://reddit3.sportshub.stream/"), mirror_key("https://reddit.sportshub2.fan"));
+        assert_eq!(
+            mirror_key("https://sportshub.stream/rugby-union-streams/"),
+            mirror_key("https://sportshub1.fan/rugby-union-streams")
+        );
+        assert_ne!(
+            mirror_key("https://sportshub.stream/rugby-union-streams/"),
+            mirror_key("https://sportshub.stream/rugby-sevens-streams/")
+        );
+        assert_eq!(mirror_key("not a url"), None);
+    }
+}
@@ -1,8 +1,10 @@
 //! This module contains the functions to get the data from the dom of the eventlist
-//! and return the data as a string
+//! and the event pages, and return the data as a string
 
 use thiserror::Error;
-use tl::{Parser, VDom};
+use tl::{HTMLTag, Parser, VDom};
+
+use crate::db::models::LinkInfo;
 
 #[derive(Error, Debug)]
 pub enum DomParseError {
@@ -14,6 +16,26 @@ pub enum DomParseError {
     Unknown,
 }
 
+/// Why we couldn't get the links of an event page, every page fails on its own
+/// so the other pages are still scraped
+#[derive(Error, Debug)]
+pub enum LinkScrapeError {
+    #[error("invalid event url {0}")]
+    InvalidUrl(String),
+    #[error("could not open the event page: {0}")]
+    Navigation(anyhow::Error),
+    #[error("the event page never loaded: {0}")]
+    NotLoaded(anyhow::Error),
+    #[error("could not read the link table: {0}")]
+    Table(anyhow::Error),
+    #[error("could not parse the link table: {0}")]
+    Parse(#[from] DomParseError),
+    #[error("could not save the links: {0}")]
+    Database(anyhow::Error),
+    #[error("the scraper crashed on this page: {0}")]
+    Panicked(String),
+}
+
 /// Get the event url from the dom of the eventlist
 /// # Arguments
 /// * `dom` - The dom of the eventlist (tl)
@@ -168,7 +190,7 @@ pub fn get_country_from_dom(dom: &VDom<'_>, parser: &Parser<'_>) -> Result<Strin
         .ok_or(DomParseError::NoAttributeFound)?
         .as_utf8_str()
         .split('/')
-        .last()
+        .next_back()
         .ok_or(DomParseError::NoAttributeFound)?
         .replace(");", "")
         .replace(".svg", "")
@@ -176,3 +198,181 @@ pub fn get_country_from_dom(dom: &VDom<'_>, parser: &Parser<'_>) -> Result<Strin
 
     Ok(q)
 }
+
+/// columns of the `lnktbj` table on the event page, counting from 0
+const STREAMER_COLUMN: usize = 0;
+const LANGUAGE_COLUMN: usize = 1;
+const QUALITY_COLUMN: usize = 2;
+const ADS_COLUMN: usize = 3;
+const CHANNEL_TYPE_COLUMN: usize = 4;
+const LINK_COLUMN: usize = 5;
+
+/// Get every stream link with its metadata from the dom of the link table on an event page
+/// # Arguments
+/// * `dom` - The dom of the `lnktbj` table (tl)
+/// * `parser` - The parser of the `lnktbj` table (tl)
+/// # Example
+/// ```
+/// use scraper::query_selectors::get_links_from_dom;
+/// let html = r#"
+/// <table class="lnktbj">
+///   <tbody>
+///     <tr>
+///       <td>StreamerOne</td>
+///       <td><img src="/img/flags/en.png" alt="English"></td>
+///       <td>HD 2500kbps</td>
+///       <td>2</td>
+///       <td>Web</td>
+///       <td><a href="https://example.com/embed/1">Play</a></td>
+///     </tr>
+///   </tbody>
+/// </table>
+/// "#;
+///
+/// let dom = tl::parse(html, tl::ParserOptions::default()).unwrap();
+/// let parser = dom.parser();
+///
+/// let links = get_links_from_dom(&dom, &parser).unwrap();
+/// assert_eq!(links.len(), 1);
+/// assert_eq!(links[0].url, "https://example.com/embed/1");
+/// assert_eq!(links[0].streamer.as_deref(), Some("StreamerOne"));
+/// assert_eq!(links[0].language.as_deref(), Some("English"));
+/// assert_eq!(links[0].quality.as_deref(), Some("HD 2500kbps"));
+/// assert_eq!(links[0].ad_count, Some(2));
+/// assert_eq!(links[0].channel_type.as_deref(), Some("Web"));
+/// ```
+pub fn get_links_from_dom(dom: &VDom<'_>, parser: &Parser<'_>) -> Result<Vec<LinkInfo>, DomParseError> {
+    let mut found = vec![];
+
+    for row in dom.query_selector("tr").ok_or(DomParseError::NotFound)? {
+        let Some(row) = row.get(parser).and_then(|r| r.as_tag()) else {
+            continue;
+        };
+
+        let cells: Vec<&HTMLTag> = row
+            .children()
+            .top()
+            .iter()
+            .filter_map(|c| c.get(parser)?.as_tag())
+            .filter(|t| t.name().as_utf8_str() == "td")
+            .collect();
+
+        // header rows and rows without a link are skipped
+        let Some(url) = cells.get(LINK_COLUMN).and_then(|c| get_href(c, parser)) else {
+            continue;
+        };
+
+        // some of them are just text, so we check for "//"
+        if !url.contains("//") {
+            continue;
+        }
+
+        found.push(LinkInfo {
+            url,
+            streamer: get_cell_text(cells.get(STREAMER_COLUMN), parser),
+            language: get_cell_text(cells.get(LANGUAGE_COLUMN), parser),
+            quality: get_cell_text(cells.get(QUALITY_COLUMN), parser),
+            ad_count: get_cell_text(cells.get(ADS_COLUMN), parser).and_then(|t| {
+                t.chars()
+                    .filter(|c| c.is_ascii_digit())
+                    .collect::<String>()
+                    .parse()
+                    .ok()
+            }),
+            channel_type: get_cell_text(cells.get(CHANNEL_TYPE_COLUMN), parser),
+        });
+    }
+
+    Ok(found)
+}
+
+/// the href of the first link inside a tag, tl gives the raw attribute so the entities are decoded here
+fn get_href(tag: &HTMLTag<'_>, parser: &Parser<'_>) -> Option<String> {
+    let href = tag
+        .query_selector(parser, "a")?
+        .next()?
+        .get(parser)?
+        .as_tag()?
+        .attributes()
+        .get("href")??
+        .as_utf8_str();
+
+    Some(decode_entities(href.trim()))
+}
+
+/// Decode the html entities of an attribute, like the `&amp;` between the query parameters of a link.
+/// Named entities other than the ones attributes need are left as they are.
+///
+/// # Example
+/// ```
+/// use scraper::query_selectors::decode_entities;
+///
+/// assert_eq!(decode_entities("https://a.b/?c=1&amp;d=2"), "https://a.b/?c=1&d=2");
+/// assert_eq!(decode_entities("&quot;x&quot; &#39;y&#x27; &nbsp;"), "\"x\" 'y' &nbsp;");
+/// ```
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        // an entity is short, a lone & is kept as it is
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 8)
+            .map(|end| &rest[1..end + 1]);
+
+        let decoded_char = entity.and_then(|e| match e {
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            _ => {
+                let code = match e.strip_prefix("#x").or(e.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => e.strip_prefix('#')?.parse().ok(),
+                };
+
+                code.and_then(char::from_u32)
+            }
+        });
+
+        match (entity, decoded_char) {
+            (Some(e), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[e.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+/// the text of a table cell, falling back to the alt/title of an image (they use flags for languages)
+fn get_cell_text(cell: Option<&&HTMLTag<'_>>, parser: &Parser<'_>) -> Option<String> {
+    let cell = cell?;
+
+    let text = cell.inner_text(parser).split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if !text.is_empty() {
+        return Some(text);
+    }
+
+    let img = cell.query_selector(parser, "img")?.next()?.get(parser)?.as_tag()?;
+    let attributes = img.attributes();
+
+    attributes
+        .get("alt")
+        .flatten()
+        .or(attributes.get("title").flatten())
+        .map(|a| a.as_utf8_str().trim().to_string())
+        .filter(|a| !a.is_empty())
+}
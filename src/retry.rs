@@ -0,0 +1,135 @@
+//! Retries with exponential backoff, so a page that times out once doesn't lose a whole sport.
+
+use std::{thread, time::Duration};
+
+use anyhow::Error;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// How often and how patiently a failing page is tried again, as a `[retry]` table in the config
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// How many times a page is tried in total, 1 never retries (default: 3)
+    pub attempts: u32,
+    /// How long to wait before the first retry, in milliseconds (default: 1000)
+    pub initial_delay_ms: u64,
+    /// How much longer every following wait is (default: 2)
+    pub backoff_factor: u32,
+    /// The longest wait between two attempts, in milliseconds (default: 30000)
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            initial_delay_ms: 1000,
+            backoff_factor: 2,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+/// What is left once every attempt failed, the error is the one of the last attempt
+#[derive(Error, Debug)]
+#[error("gave up after {attempts} attempts: {error}")]
+pub struct GaveUp {
+    pub attempts: u32,
+    pub error: Error,
+}
+
+impl RetryPolicy {
+    /// Try only once
+    pub fn never() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// How long to wait before retry number `retry`, starting at 1
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use scraper::retry::RetryPolicy;
+    ///
+    /// let policy = RetryPolicy::default();
+    /// assert_eq!(policy.delay(1), Duration::from_secs(1));
+    /// assert_eq!(policy.delay(3), Duration::from_secs(4));
+    /// assert_eq!(policy.delay(10), Duration::from_secs(30));
+    /// ```
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = (self.backoff_factor.max(1) as u64).saturating_pow(retry.saturating_sub(1));
+        let delay = self.initial_delay_ms.saturating_mul(factor).min(self.max_delay_ms);
+
+        Duration::from_millis(delay)
+    }
+
+    /// Call `f` until it works or we run out of attempts, waiting longer and longer in between
+    ///
+    /// # Arguments
+    /// * `f` - The fallible call, it gets the attempt number starting at 1
+    ///
+    /// # Example
+    /// ```
+    /// use scraper::retry::RetryPolicy;
+    ///
+    /// let policy = RetryPolicy {
+    ///     initial_delay_ms: 0,
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let page = policy.run(|attempt| if attempt < 3 { Err(anyhow::anyhow!("timeout")) } else { Ok("<html>") });
+    /// assert_eq!(page.unwrap(), "<html>");
+    ///
+    /// let failed = policy.run(|_| Err::<(), _>(anyhow::anyhow!("timeout"))).unwrap_err();
+    /// assert_eq!(failed.attempts, 3);
+    /// ```
+    pub fn run<T>(&self, mut f: impl FnMut(u32) -> Result<T, Error>) -> Result<T, GaveUp> {
+        let attempts = self.attempts.max(1);
+        let mut attempt = 1;
+
+        loop {
+            match f(attempt) {
+                Ok(value) => return Ok(value),
+                Err(error) if attempt >= attempts => return Err(GaveUp { attempts, error }),
+                Err(_) => {
+                    thread::sleep(self.delay(attempt));
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delay_is_capped() {
+        let policy = RetryPolicy {
+            attempts: 100,
+            backoff_factor: 10,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.delay(2), Duration::from_secs(10));
+        assert_eq!(policy.delay(100), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_never_retries() {
+        let mut calls = 0;
+        let result = RetryPolicy::never().run(|_| {
+            calls += 1;
+            Err::<(), _>(anyhow::anyhow!("dead subdomain"))
+        });
+
+        assert_eq!(calls, 1);
+        assert_eq!(result.unwrap_err().to_string(), "gave up after 1 attempts: dead subdomain");
+    }
+}
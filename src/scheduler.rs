@@ -0,0 +1,336 @@
+//! Runs the scrapes on a schedule, instead of cron.
+//!
+//! The events are scraped every `events_minutes`, the links every `links_minutes` and
+//! checked every `checks_minutes`, both more often (`kickoff_minutes`) when an event is
+//! about to start. The finished events are archived every `prune_minutes`.
+//! Every run takes a lock file first, so the daemon and a manual
+//! `sportshub data update` never scrape at the same time.
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions, TryLockError},
+    io::Write,
+    path::Path,
+    thread,
+};
+
+use anyhow::Error;
+use chrono::{Duration, NaiveDateTime, Utc};
+
+use crate::{
+    config::{Config, ScheduleConfig},
+    constants::sports::Catalogue,
+    db,
+    fetcher::HttpFetcher,
+    link_checker::{self, HttpProber},
+    scrape,
+};
+
+/// The longest the daemon sleeps, so new kickoffs are picked up
+const MAX_SLEEP_SECONDS: i64 = 60;
+
+/// Stops two scrape runs from running at the same time, the lock is released when this is dropped
+///
+/// It is an advisory lock on the file, so the OS releases it when a run crashes
+#[derive(Debug)]
+pub struct RunLock {
+    // the file is never removed, a run could lock the removed file while another one creates a new one
+    _file: File,
+}
+
+impl RunLock {
+    /// Take the lock at `path`, the file is created if it doesn't exist
+    ///
+    /// # Returns
+    /// `None` if another run holds the lock
+    ///
+    /// # Example
+    /// ```
+    /// use scraper::scheduler::RunLock;
+    ///
+    /// let path = std::env::temp_dir().join(format!("sportshub-doctest-{}.lock", std::process::id()));
+    ///
+    /// let lock = RunLock::acquire(&path).unwrap();
+    /// assert!(lock.is_some());
+    /// assert!(RunLock::acquire(&path).unwrap().is_none());
+    /// # drop(lock);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn acquire(path: &Path) -> Result<Option<RunLock>, Error> {
+        let locked = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(TryLockError::Error)
+            .and_then(|file| file.try_lock().map(|_| file));
+
+        let mut file = match locked {
+            Ok(file) => file,
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => {
+                return Err(anyhow::anyhow!("could not lock {}: {}", path.display(), e));
+            }
+        };
+
+        // only there to tell who holds the lock
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+
+        Ok(Some(RunLock { _file: file }))
+    }
+}
+
+/// The jobs the daemon runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Job {
+    /// Scrape the events of every sport
+    Events,
+    /// Scrape the links of the events that have none, and of the upcoming and live ones
+    Links,
+    /// Check that the stream links still work
+    Checks,
+    /// Archive the finished events and delete the old archived ones
+    Prune,
+}
+
+impl Job {
+    pub const ALL: [Job; 4] = [Job::Events, Job::Links, Job::Checks, Job::Prune];
+}
+
+/// Decides which jobs are due, it only remembers when each job last ran
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    schedule: ScheduleConfig,
+    last_run: HashMap<Job, NaiveDateTime>,
+}
+
+impl Scheduler {
+    pub fn new(schedule: ScheduleConfig) -> Scheduler {
+        Scheduler {
+            schedule,
+            last_run: HashMap::new(),
+        }
+    }
+
+    /// How long to wait between two runs of `job`, shorter for the links when an event is about to start
+    ///
+    /// # Arguments
+    /// * `now` - The current time
+    /// * `next_kickoff` - When the next event starts, see [`db::helpers::get_next_kickoff`]
+    pub fn interval(&self, job: Job, now: NaiveDateTime, next_kickoff: Option<NaiveDateTime>) -> Duration {
+        let minutes = match job {
+            Job::Events => self.schedule.events_minutes,
+            Job::Links | Job::Checks if self.near_kickoff(now, next_kickoff) => self.schedule.kickoff_minutes,
+            Job::Links => self.schedule.links_minutes,
+            Job::Checks => self.schedule.checks_minutes,
+            Job::Prune => self.schedule.prune_minutes,
+        };
+
+        Duration::minutes(minutes.max(1) as i64)
+    }
+
+    fn near_kickoff(&self, now: NaiveDateTime, next_kickoff: Option<NaiveDateTime>) -> bool {
+        next_kickoff.is_some_and(|k| k - now <= Duration::minutes(self.schedule.kickoff_window_minutes as i64))
+    }
+
+    /// When `job` should run next, right away if it never ran.
+    /// The links are also due as soon as the window before the next kickoff starts.
+    pub fn next_run(&self, job: Job, now: NaiveDateTime, next_kickoff: Option<NaiveDateTime>) -> NaiveDateTime {
+        let Some(last) = self.last_run.get(&job) else {
+            return now;
+        };
+
+        let next = *last + self.interval(job, now, next_kickoff);
+
+        match (job, next_kickoff) {
+            (Job::Links | Job::Checks, Some(kickoff)) => {
+                let window_start = kickoff - Duration::minutes(self.schedule.kickoff_window_minutes as i64);
+                // only if we haven't run since the window started
+                if window_start > *last {
+                    next.min(window_start)
+                } else {
+                    next
+                }
+            }
+            _ => next,
+        }
+    }
+
+    /// The jobs that should run at `now`, in the order they should run
+    pub fn due(&self, now: NaiveDateTime, next_kickoff: Option<NaiveDateTime>) -> Vec<Job> {
+        Job::ALL
+            .into_iter()
+            .filter(|job| self.next_run(*job, now, next_kickoff) <= now)
+            .collect()
+    }
+
+    /// Remember that `job` ran at `at`
+    pub fn mark_run(&mut self, job: Job, at: NaiveDateTime) {
+        self.last_run.insert(job, at);
+    }
+}
+
+/// Run a single job against the database at `database_url`
+///
+/// # Arguments
+/// * `config` - The schedule, the retries, the timezone and the retention of the archived events
+/// * `catalogue` - The sports, the enabled ones are scraped and the durations decide what is archived
+pub fn run_job(job: Job, database_url: &str, config: &Config, catalogue: &Catalogue) -> Result<(), Error> {
+    match job {
+        Job::Events => {
+            let fetcher = HttpFetcher::new().with_utc_offset(config.utc_offset()?);
+            let landing_page = config.sports.landing_page.as_deref();
+            let sports = catalogue.enabled();
+            let report = scrape::scrape_events(database_url, &fetcher, &sports, &config.retry, landing_page)?;
+            println!("Found {} events", report.events_found());
+
+            for failure in &report.errors {
+                eprintln!("Failed: {}", failure);
+            }
+
+            Ok(())
+        }
+        Job::Links => {
+            let report = scrape::update_streams(database_url, catalogue, config.schedule.tabs, true)?;
+            println!("Found {} links", report.links_found);
+
+            for failure in &report.errors {
+                eprintln!("Failed: {}", failure);
+            }
+
+            Ok(())
+        }
+        Job::Checks => {
+            let mut conn = db::helpers::establish_connection(database_url)?;
+            let now = Utc::now().naive_utc();
+            let prober = HttpProber::new(link_checker::DEFAULT_TIMEOUT);
+
+            let summary = link_checker::check_links(&mut conn, &prober, link_checker::DEFAULT_WORKERS, now, now)?;
+            println!("Checked {} links, {} dead", summary.checked, summary.dead);
+
+            Ok(())
+        }
+        Job::Prune => {
+            let mut conn = db::helpers::establish_connection(database_url)?;
+
+            let report = db::helpers::prune_streams(&mut conn, catalogue, Utc::now().naive_utc(), config.retention())?;
+            println!("Archived {} events, deleted {} archived events", report.archived, report.purged);
+
+            Ok(())
+        }
+    }
+}
+
+/// Run the jobs on schedule forever, a failing job is logged and tried again at its next run
+///
+/// # Arguments
+/// * `database_url` - Where the database is, see [`crate::config::Config::database_url`]
+/// * `config` - When to run the jobs and how, the lock shared with the other scrape runs
+///   is [`crate::config::Config::lock_file`]
+/// * `catalogue` - The sports, see [`run_job`]
+pub fn run_forever(database_url: &str, config: &Config, catalogue: &Catalogue) -> Result<(), Error> {
+    let lock_file = config.lock_file(database_url);
+
+    let mut scheduler = Scheduler::new(config.schedule.clone());
+    let mut conn = db::helpers::establish_connection(database_url)?;
+
+    loop {
+        let now = Utc::now().naive_utc();
+        let next_kickoff = db::helpers::get_next_kickoff(&mut conn, now)?;
+        let mut locked_out = false;
+
+        for job in scheduler.due(now, next_kickoff) {
+            let Some(_lock) = RunLock::acquire(&lock_file)? else {
+                println!("Another run holds {}, {:?} waits", lock_file.display(), job);
+                locked_out = true;
+                break;
+            };
+
+            if let Err(e) = run_job(job, database_url, config, catalogue) {
+                eprintln!("{:?} failed: {}", job, e);
+            }
+
+            // a failed job waits for its next run too, so a broken site isn't hammered
+            scheduler.mark_run(job, now);
+        }
+
+        let now = Utc::now().naive_utc();
+        let wake_up = Job::ALL
+            .into_iter()
+            .map(|job| scheduler.next_run(job, now, next_kickoff))
+            .min()
+            .unwrap_or(now);
+
+        // the jobs that waited for the lock are still due, no point asking again straight away
+        let sleep = if locked_out {
+            MAX_SLEEP_SECONDS
+        } else {
+            (wake_up - now).num_seconds().clamp(1, MAX_SLEEP_SECONDS)
+        };
+        thread::sleep(std::time::Duration::from_secs(sleep as u64));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(minutes: i64) -> NaiveDateTime {
+        NaiveDateTime::from_timestamp_opt(1706702400, 0).unwrap() + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn test_everything_runs_first() {
+        let scheduler = Scheduler::new(ScheduleConfig::default());
+
+        assert_eq!(scheduler.due(at(0), None), Job::ALL.to_vec());
+    }
+
+    #[test]
+    fn test_intervals() {
+        let mut scheduler = Scheduler::new(ScheduleConfig::default());
+        for job in Job::ALL {
+            scheduler.mark_run(job, at(0));
+        }
+
+        assert!(scheduler.due(at(10), None).is_empty());
+        assert_eq!(scheduler.due(at(15), None), vec![Job::Links]);
+        assert_eq!(scheduler.due(at(60), None), Job::ALL.to_vec());
+    }
+
+    #[test]
+    fn test_denser_before_kickoff() {
+        let mut scheduler = Scheduler::new(ScheduleConfig::default());
+        for job in Job::ALL {
+            scheduler.mark_run(job, at(0));
+        }
+
+        // the window before a kickoff at 40 starts at 10, the links run then and every 5 minutes after
+        let kickoff = Some(at(40));
+        assert!(scheduler.due(at(9), kickoff).is_empty());
+        assert_eq!(scheduler.due(at(10), kickoff), vec![Job::Links, Job::Checks]);
+
+        scheduler.mark_run(Job::Links, at(10));
+        assert_eq!(scheduler.next_run(Job::Links, at(12), kickoff), at(15));
+    }
+
+    #[test]
+    fn test_lock() {
+        let path = std::env::temp_dir().join(format!("sportshub-test-{}.lock", std::process::id()));
+
+        let lock = RunLock::acquire(&path).unwrap();
+        assert!(lock.is_some());
+        assert!(RunLock::acquire(&path).unwrap().is_none());
+
+        // dropping the lock releases it, the file stays
+        drop(lock);
+        assert!(path.exists());
+
+        let lock = RunLock::acquire(&path).unwrap();
+        assert!(lock.is_some());
+
+        drop(lock);
+        let _ = std::fs::remove_file(&path);
+    }
+}
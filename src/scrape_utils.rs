@@ -1,6 +1,10 @@
+use std::path::Path;
+
 use anyhow::Error;
 use headless_chrome::Browser;
 
+use crate::fetcher::Fetcher;
+
 /// Create a new browser for use in scraping
 ///
 /// # Arguments
@@ -10,7 +14,7 @@ use headless_chrome::Browser;
 ///  * `Result<Browser, Error>` - The browser to use for scraping
 ///
 /// # Examples
-/// ```
+/// ```no_run
 /// use scraper::scrape_utils::create_browser;
 ///
 /// let browser = create_browser(true).unwrap();
@@ -37,7 +41,7 @@ pub fn create_browser(headless: bool) -> Result<Browser, Error> {
 /// * `Result<(), Error>` - Whether the tabs were closed successfully
 ///
 /// # Examples
-/// ```
+/// ```no_run
 /// use scraper::scrape_utils::{close_tabs, create_browser};
 ///
 /// let browser = create_browser(true).unwrap();
@@ -55,3 +59,37 @@ pub fn close_tabs(browser: &Browser) -> Result<(), Error> {
 
     Ok(())
 }
+
+
+/// Save the html of a live page to a file, so it can be used as a test fixture
+/// when the site changes
+///
+/// # Arguments
+/// * `fetcher` - The backend used to download the page
+/// * `url` - The page to save
+/// * `path` - Where to save it, usually in `tests/fixtures/`
+///
+/// # Returns
+/// * `Result<usize, Error>` - How many bytes were written
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+///
+/// use scraper::{fetcher::HttpFetcher, scrape_utils::record_fixture};
+///
+/// let fetcher = HttpFetcher::new();
+/// record_fixture(
+///     &fetcher,
+///     "https://reddit3.sportshub.stream/",
+///     Path::new("tests/fixtures/schedule_football.html"),
+/// )
+/// .unwrap();
+/// ```
+pub fn record_fixture(fetcher: &dyn Fetcher, url: &str, path: &Path) -> Result<usize, Error> {
+    let html = fetcher.fetch_page(url)?;
+
+    std::fs::write(path, &html)?;
+
+    Ok(html.len())
+}
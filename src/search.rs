@@ -0,0 +1,196 @@
+//! Fuzzy team search.
+//!
+//! Scraped names are not consistent ("Man Utd" vs "Manchester United") and some of them
+//! even use Cyrillic or Greek letters that look like latin ones, so we normalize both the
+//! query and the names before comparing them and rank the matches by how close they are.
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Matches scoring lower than this are not returned by default
+pub const DEFAULT_THRESHOLD: f64 = 0.8;
+
+/// Map a Cyrillic or Greek letter that looks like a latin one to that latin letter
+fn confusable(c: char) -> char {
+    match c {
+        // cyrillic
+        'а' | 'А' => 'a',
+        'в' | 'В' => 'b',
+        'с' | 'С' => 'c',
+        'ԁ' => 'd',
+        'е' | 'Е' | 'ё' | 'Ё' => 'e',
+        'һ' | 'Н' => 'h',
+        'і' | 'І' => 'i',
+        'ј' | 'Ј' => 'j',
+        'к' | 'К' => 'k',
+        'м' | 'М' => 'm',
+        'о' | 'О' => 'o',
+        'р' | 'Р' => 'p',
+        'ѕ' | 'Ѕ' => 's',
+        'т' | 'Т' => 't',
+        'у' | 'У' => 'y',
+        'х' | 'Х' => 'x',
+        // greek
+        'α' | 'Α' => 'a',
+        'β' | 'Β' => 'b',
+        'ε' | 'Ε' => 'e',
+        'η' | 'Η' => 'h',
+        'ι' | 'Ι' => 'i',
+        'κ' | 'Κ' => 'k',
+        'Μ' => 'm',
+        'ν' | 'Ν' => 'n',
+        'ο' | 'Ο' => 'o',
+        'ρ' | 'Ρ' => 'p',
+        'τ' | 'Τ' => 't',
+        'υ' | 'Υ' => 'y',
+        'χ' | 'Χ' => 'x',
+        'Ζ' => 'z',
+        _ => c,
+    }
+}
+
+/// Normalize a name so it can be compared with another one:
+/// look-alike letters become latin, accents are removed, everything is lowercase
+/// and anything that isn't a letter or a digit becomes a single space
+///
+/// # Arguments
+/// * `name` - The name to normalize
+///
+/// # Example
+/// ```
+/// use scraper::search::normalize;
+///
+/// assert_eq!(normalize("tоttеnhаm"), "tottenham");
+/// assert_eq!(normalize("Atlético  Madrid"), "atletico madrid");
+/// assert_eq!(normalize("Brighton & Hove-Albion"), "brighton hove albion");
+/// ```
+pub fn normalize(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(confusable)
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whether `short` could be an abbreviation of `long`,
+/// they start with the same letter and the letters of `short` are all in `long` in order ("utd" and "united")
+fn is_abbreviation(short: &str, long: &str) -> bool {
+    if short.len() < 2 || short.chars().next() != long.chars().next() {
+        return false;
+    }
+
+    let mut long_chars = long.chars();
+
+    short.chars().all(|c| long_chars.any(|l| l == c))
+}
+
+/// How well a single query word matches a single name word, from 0 to 1
+fn word_score(query: &str, word: &str) -> f64 {
+    if query == word {
+        1.0
+    } else if word.starts_with(query) {
+        0.9
+    } else if is_abbreviation(query, word) {
+        0.85
+    } else {
+        strsim::jaro_winkler(query, word)
+    }
+}
+
+/// How well `query` matches `name`, from 0 (nothing in common) to 1 (same name once normalized)
+///
+/// # Arguments
+/// * `query` - What the user searched for
+/// * `name` - A team name from the database
+///
+/// # Example
+/// ```
+/// use scraper::search::score;
+///
+/// assert_eq!(score("TOTTENHAM", "tоttеnhаm"), 1.0);
+/// assert!(score("Man Utd", "Manchester United") > 0.8);
+/// assert!(score("Man Utd", "Tottenham") < 0.8);
+/// ```
+pub fn score(query: &str, name: &str) -> f64 {
+    let query = normalize(query);
+    let name = normalize(name);
+
+    if query.is_empty() || name.is_empty() {
+        return 0.0;
+    }
+
+    if query == name {
+        return 1.0;
+    }
+
+    let words: Vec<&str> = name.split(' ').collect();
+
+    // every query word is matched with its best word in the name
+    let query_words: Vec<&str> = query.split(' ').collect();
+    let words_score = query_words
+        .iter()
+        .map(|q| words.iter().map(|w| word_score(q, w)).fold(0.0, f64::max))
+        .sum::<f64>()
+        / query_words.len() as f64;
+
+    // a name containing the whole query as words is a good match too ("chelsea" in "chelsea fc"),
+    // the letters alone aren't ("man" in "germany")
+    let contains_score = if format!(" {} ", name).contains(&format!(" {} ", query)) {
+        0.95
+    } else {
+        0.0
+    };
+
+    words_score
+        .max(contains_score)
+        .max(strsim::jaro_winkler(&query, &name))
+        .min(0.99)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_confusables() {
+        assert_eq!(normalize("brеntfоrd"), "brentford");
+        assert_eq!(normalize("ΑΕΚ Athens"), "aek athens");
+    }
+
+    #[test]
+    fn test_normalize_accents() {
+        assert_eq!(normalize("Grêmio"), "gremio");
+        assert_eq!(normalize("Borussia Mönchengladbach"), "borussia monchengladbach");
+    }
+
+    #[test]
+    fn test_abbreviations() {
+        assert!(score("Man Utd", "Manchester United") > DEFAULT_THRESHOLD);
+        assert!(score("Wolves", "Wolverhampton Wanderers") > DEFAULT_THRESHOLD);
+    }
+
+    #[test]
+    fn test_ranking() {
+        let utd = score("manchester utd", "Manchester United");
+        let city = score("manchester utd", "Manchester City");
+
+        assert!(utd > city);
+    }
+
+    #[test]
+    fn test_unrelated() {
+        assert!(score("Arsenal", "Chelsea") < DEFAULT_THRESHOLD);
+        assert_eq!(score("", "Chelsea"), 0.0);
+    }
+
+    #[test]
+    fn test_contains_whole_words() {
+        assert!(score("man", "Germany") < DEFAULT_THRESHOLD);
+        assert!(score("man", "Roman") < DEFAULT_THRESHOLD);
+        assert!(score("man", "Manchester United") > score("man", "Germany"));
+    }
+}
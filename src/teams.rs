@@ -0,0 +1,52 @@
+//! Known spellings of teams.
+//!
+//! The scraped names of a team change between sports pages and days, so every name goes through
+//! an alias table before being saved. The aliases come from `data/teams.toml`, which is bundled in
+//! the binary, and from the `[[teams]]` tables of the config file.
+
+use anyhow::Error;
+use serde::Deserialize;
+
+/// The aliases bundled with the binary
+pub const BUNDLED_TEAMS: &str = include_str!("data/teams.toml");
+
+/// A team and the other names it is known by
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TeamAliases {
+    /// The canonical name, the one the api shows
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TeamsFile {
+    #[serde(default)]
+    teams: Vec<TeamAliases>,
+}
+
+/// Parse a toml file made of `[[teams]]` tables
+///
+/// # Arguments
+/// * `contents` - The contents of the file
+///
+/// # Example
+/// ```
+/// use scraper::teams::parse_teams;
+///
+/// let teams = parse_teams(r#"
+/// [[teams]]
+/// name = "Manchester United"
+/// aliases = ["Man Utd"]
+/// "#).unwrap();
+///
+/// assert_eq!(teams[0].aliases, vec!["Man Utd".to_string()]);
+/// ```
+pub fn parse_teams(contents: &str) -> Result<Vec<TeamAliases>, Error> {
+    Ok(toml::from_str::<TeamsFile>(contents)?.teams)
+}
+
+/// The aliases bundled with the binary
+pub fn bundled_teams() -> Result<Vec<TeamAliases>, Error> {
+    parse_teams(BUNDLED_TEAMS)
+}
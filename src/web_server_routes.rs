@@ -1,16 +1,106 @@
 //! This module contains the web server for the API.
 //! It uses the rocket framework.
 
-use db::models::Stream;
-use rocket::{get, response::content::RawHtml, routes, serde::json::Json, Rocket};
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use db::models::{EventLinkHistory, ScrapeRun, StreamWithLinks, Team};
+use diesel::SqliteConnection;
+use juniper::http::GraphQLRequest;
+use rocket::{
+    catch,
+    catchers,
+    get,
+    http::Status,
+    post,
+    response::{
+        content::{RawHtml, RawJson},
+        Responder,
+    },
+    routes,
+    serde::json::Json,
+    tokio::task::{spawn_blocking, JoinError},
+    Build,
+    FromForm,
+    Request,
+    Rocket,
+    State,
+};
+use serde::Serialize;
+use thiserror::Error;
 
 use crate::{
-    constants::{self, sports::Sport},
-    db::{self, helpers::LeagueWithCountry},
+    constants::sports::{Catalogue, Sport},
+    db::{
+        self,
+        helpers::{page_size, DbPool, LeagueWithCountry, SortOrder, StreamCursor, StreamFilter, DEFAULT_PAGE_SIZE},
+    },
+    graphql::{self, Schema},
+    search,
 };
 
 pub const INDEX_HTML: &str = include_str!("html/index.html");
 
+/// How old the last successful scrape can be before `/status` reports the data as stale
+pub const DEFAULT_STALE_AFTER_MINUTES: i64 = 120;
+
+/// Errors returned by the handlers, they are sent to the client as
+/// `{"error": "..."}` with a matching status code instead of panicking
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("database error: {0}")]
+    Database(#[from] anyhow::Error),
+    #[error("no database connection available: {0}")]
+    Pool(#[from] diesel::r2d2::PoolError),
+    #[error("the database query didn't finish: {0}")]
+    Blocking(#[from] JoinError),
+}
+
+impl ApiError {
+    fn status(&self) -> Status {
+        match self {
+            ApiError::NotFound(_) => Status::NotFound,
+            ApiError::BadRequest(_) => Status::BadRequest,
+            ApiError::Database(_) | ApiError::Pool(_) | ApiError::Blocking(_) => Status::InternalServerError,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let status = self.status();
+
+        (status, Json(ErrorBody { error: self.to_string() })).respond_to(request)
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// Run `query` with a pooled connection on the blocking threads, the pool and diesel block
+/// and would hold up the async workers serving the other requests
+async fn with_conn<T, F>(pool: &DbPool, query: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut SqliteConnection) -> Result<T, ApiError> + Send + 'static,
+{
+    let pool = pool.clone();
+
+    spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        query(&mut conn)
+    })
+    .await?
+}
+
 #[get("/")]
 async fn get_route_desc() -> RawHtml<String> {
     let out = INDEX_HTML.replace("{{version}}", env!("CARGO_PKG_VERSION")).to_owned();
@@ -24,111 +114,582 @@ async fn get_version() -> &'static str {
 }
 
 #[get("/all")]
-async fn get_all_streams() -> Json<Vec<Stream>> {
-    let mut conn = db::helpers::establish_connection().unwrap();
-    let streams = db::helpers::get_streams(&mut conn).unwrap();
+async fn get_all_streams(pool: &State<DbPool>, catalogue: &State<Arc<Catalogue>>) -> ApiResult<Vec<StreamWithLinks>> {
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::get_streams(conn)?;
 
-    Json(streams)
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
+
+    Ok(Json(streams))
 }
 
 #[get("/active")]
-async fn get_active_streams() -> Json<Vec<Stream>> {
-    let mut conn = db::helpers::establish_connection().unwrap();
-    let streams = db::helpers::get_linked_streams(&mut conn).unwrap();
+async fn get_active_streams(
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<Vec<StreamWithLinks>> {
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::get_linked_streams(conn)?;
+
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
+
+    Ok(Json(streams))
+}
+
+#[get("/live")]
+async fn get_live_streams(pool: &State<DbPool>, catalogue: &State<Arc<Catalogue>>) -> ApiResult<Vec<StreamWithLinks>> {
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::get_live_streams(conn, &catalogue, Utc::now().naive_utc())?;
+
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
+
+    Ok(Json(streams))
+}
+
+#[get("/upcoming")]
+async fn get_upcoming_streams(
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<Vec<StreamWithLinks>> {
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::get_upcoming_streams(conn, Utc::now().naive_utc())?;
+
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
 
-    Json(streams)
+    Ok(Json(streams))
 }
 
 #[get("/id/<id>")]
-async fn get_stream_by_id(id: i32) -> Json<Vec<Stream>> {
-    let mut conn = db::helpers::establish_connection().unwrap();
-    let streams = db::helpers::get_streams_by_id(&mut conn, id).unwrap();
+async fn get_stream_by_id(
+    id: i32,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<Vec<StreamWithLinks>> {
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::get_streams_by_id(conn, id)?;
+
+        if streams.is_empty() {
+            return Err(ApiError::NotFound(format!("no event with id {}", id)));
+        }
+
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
 
-    Json(streams)
+    Ok(Json(streams))
+}
+
+#[get("/id/<id>/links")]
+async fn get_link_history(
+    id: i32,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<EventLinkHistory> {
+    let catalogue = catalogue.inner().clone();
+
+    match with_conn(pool, move |conn| Ok(db::helpers::get_link_history(conn, &catalogue, id)?)).await? {
+        Some(history) => Ok(Json(history)),
+        None => Err(ApiError::NotFound(format!("no event with id {}", id))),
+    }
 }
 
 #[get("/sport/<sport>")]
-async fn get_streams_by_sport(sport: &str) -> Json<Vec<Stream>> {
-    let mut conn = db::helpers::establish_connection().unwrap();
-    let streams = db::helpers::get_streams_by_sport(&mut conn, sport.to_owned()).unwrap();
+async fn get_streams_by_sport(
+    sport: String,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<Vec<StreamWithLinks>> {
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::get_streams_by_sport(conn, sport)?;
 
-    Json(streams)
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
+
+    Ok(Json(streams))
 }
 
 #[get("/team/home/<team>")]
-async fn get_streams_by_home_team(team: &str) -> Json<Vec<Stream>> {
-    let mut conn = db::helpers::establish_connection().unwrap();
-    let streams = db::helpers::get_streams_by_home_team(&mut conn, team.to_owned()).unwrap();
+async fn get_streams_by_home_team(
+    team: String,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<Vec<StreamWithLinks>> {
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::get_streams_by_home_team(conn, team)?;
+
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
 
-    Json(streams)
+    Ok(Json(streams))
 }
 
 #[get("/team/away/<team>")]
-async fn get_streams_by_away_team(team: &str) -> Json<Vec<Stream>> {
-    let mut conn = db::helpers::establish_connection().unwrap();
-    let streams = db::helpers::get_streams_by_away_team(&mut conn, team.to_owned()).unwrap();
+async fn get_streams_by_away_team(
+    team: String,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<Vec<StreamWithLinks>> {
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::get_streams_by_away_team(conn, team)?;
+
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
 
-    Json(streams)
+    Ok(Json(streams))
 }
 
 #[get("/team/<team>")]
-async fn get_streams_by_either_team(team: &str) -> Json<Vec<Stream>> {
-    let mut conn = db::helpers::establish_connection().unwrap();
-    let streams = db::helpers::get_streams_by_either_team(&mut conn, team.to_owned()).unwrap();
+async fn get_streams_by_either_team(
+    team: String,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<Vec<StreamWithLinks>> {
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::get_streams_by_either_team(conn, team)?;
 
-    Json(streams)
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
+
+    Ok(Json(streams))
 }
 
 #[get("/leagues/<league>")]
-async fn get_streams_by_league(league: &str) -> Json<Vec<Stream>> {
-    let mut conn = db::helpers::establish_connection().unwrap();
-    let streams = db::helpers::get_streams_by_league(&mut conn, league.to_owned()).unwrap();
+async fn get_streams_by_league(
+    league: String,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<Vec<StreamWithLinks>> {
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::get_streams_by_league(conn, league)?;
 
-    Json(streams)
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
+
+    Ok(Json(streams))
+}
+
+/// The query parameters of `/streams`, every one of them is optional
+#[derive(Debug, FromForm)]
+struct StreamsQuery {
+    sport: Option<String>,
+    league: Option<String>,
+    country: Option<String>,
+    team: Option<String>,
+    /// unix timestamp, only events starting at or after it
+    from: Option<i64>,
+    /// unix timestamp, only events starting at or before it
+    to: Option<i64>,
+    /// only events starting in the next `within` minutes
+    within: Option<i64>,
+    has_links: Option<bool>,
+    /// `asc` or `desc`, by start time
+    sort: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// the `next_cursor` of the previous page
+    cursor: Option<String>,
+}
+
+impl StreamsQuery {
+    fn to_filter(&self) -> Result<StreamFilter, ApiError> {
+        let timestamp = |name: &str, ts: i64| {
+            NaiveDateTime::from_timestamp_opt(ts, 0).ok_or(ApiError::BadRequest(format!("invalid {} {}", name, ts)))
+        };
+
+        let mut starts_after = self.from.map(|ts| timestamp("from", ts)).transpose()?;
+        let mut starts_before = self.to.map(|ts| timestamp("to", ts)).transpose()?;
+
+        if let Some(minutes) = self.within {
+            if minutes < 0 {
+                return Err(ApiError::BadRequest(format!("invalid within {}", minutes)));
+            }
+
+            let now = Utc::now().naive_utc();
+            let end = now + Duration::minutes(minutes);
+
+            // within narrows down from and to, it doesn't replace them
+            starts_after = Some(starts_after.map_or(now, |t| t.max(now)));
+            starts_before = Some(starts_before.map_or(end, |t| t.min(end)));
+        }
+
+        let order = match self.sort.as_deref() {
+            None | Some("asc") => SortOrder::Ascending,
+            Some("desc") => SortOrder::Descending,
+            Some(other) => {
+                return Err(ApiError::BadRequest(format!(
+                    "invalid sort {}, expected asc or desc",
+                    other
+                )))
+            }
+        };
+
+        let limit = page_size(self.limit, self.offset).map_err(ApiError::BadRequest)?;
+
+        if self.cursor.is_some() && self.offset.is_some() {
+            return Err(ApiError::BadRequest("use either cursor or offset, not both".to_owned()));
+        }
+
+        let cursor = self
+            .cursor
+            .as_deref()
+            .map(str::parse::<StreamCursor>)
+            .transpose()
+            .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+        Ok(StreamFilter {
+            sport: self.sport.clone(),
+            league: self.league.clone(),
+            country: self.country.clone(),
+            team: self.team.clone(),
+            starts_after,
+            starts_before,
+            has_links: self.has_links,
+            order,
+            limit: Some(limit),
+            offset: self.offset,
+            cursor,
+        })
+    }
+}
+
+/// A page of `/streams`, pass `next_cursor` as `cursor` (or `next_offset` as `offset`)
+/// to get the next one. Both are null on the last page, and `next_offset` on the pages asked for by cursor.
+#[derive(Debug, Serialize)]
+pub struct StreamPage {
+    pub streams: Vec<StreamWithLinks>,
+    pub next_cursor: Option<String>,
+    pub next_offset: Option<i64>,
+}
+
+#[get("/streams?<query..>")]
+async fn get_filtered_streams(
+    query: StreamsQuery,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<StreamPage> {
+    let filter = query.to_filter()?;
+    let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+
+    let catalogue = catalogue.inner().clone();
+    let page = with_conn(pool, move |conn| {
+        // we ask for one more than the limit to know if there is a next page
+        let mut streams = db::helpers::get_filtered_streams(conn, &filter.clone().limit(limit + 1))?;
+        let has_more = streams.len() as i64 > limit;
+        streams.truncate(limit as usize);
+
+        let (next_cursor, next_offset) = if has_more {
+            (
+                streams.last().and_then(StreamCursor::after).map(|c| c.to_string()),
+                // an offset from a cursor page would count from the wrong place
+                filter.cursor.is_none().then(|| filter.offset.unwrap_or(0) + limit),
+            )
+        } else {
+            (None, None)
+        };
+
+        Ok(StreamPage {
+            streams: db::helpers::attach_links(conn, &catalogue, streams)?,
+            next_cursor,
+            next_offset,
+        })
+    })
+    .await?;
+
+    Ok(Json(page))
+}
+
+#[get("/archive?<from>&<to>")]
+async fn get_archived_streams(
+    from: Option<i64>,
+    to: Option<i64>,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<Vec<StreamWithLinks>> {
+    let timestamp = |name: &str, ts: i64| {
+        NaiveDateTime::from_timestamp_opt(ts, 0).ok_or(ApiError::BadRequest(format!("invalid {} {}", name, ts)))
+    };
+
+    let from = from.map(|ts| timestamp("from", ts)).transpose()?;
+    let to = to.map(|ts| timestamp("to", ts)).transpose()?;
+
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::get_archived_streams(conn, from, to)?;
+
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
+
+    Ok(Json(streams))
+}
+
+#[get("/search?<q>")]
+async fn search_streams(
+    q: &str,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> ApiResult<Vec<StreamWithLinks>> {
+    if search::normalize(q).is_empty() {
+        return Err(ApiError::BadRequest("empty search query".to_owned()));
+    }
+
+    let q = q.to_owned();
+    let catalogue = catalogue.inner().clone();
+    let streams = with_conn(pool, move |conn| {
+        let streams = db::helpers::search_streams(conn, &q, search::DEFAULT_THRESHOLD)?
+            .into_iter()
+            .map(|(s, _)| s)
+            .collect();
+
+        Ok(db::helpers::attach_links(conn, &catalogue, streams)?)
+    })
+    .await?;
+
+    Ok(Json(streams))
+}
+
+/// What `/status` returns, monitoring only needs to look at the status code
+#[derive(Serialize)]
+struct ScrapeStatus {
+    last_run: Option<ScrapeRun>,
+    last_events_run: Option<ScrapeRun>,
+    last_links_run: Option<ScrapeRun>,
+    events: i64,
+    links: i64,
+    stale: bool,
+    stale_after_minutes: i64,
+}
+
+/// The last scrapes and how much we have, with a 503 when the events or the links weren't scraped
+/// successfully in the last `stale_after` minutes
+#[get("/status?<stale_after>")]
+async fn get_status(stale_after: Option<i64>, pool: &State<DbPool>) -> Result<(Status, Json<ScrapeStatus>), ApiError> {
+    let stale_after = stale_after.unwrap_or(DEFAULT_STALE_AFTER_MINUTES);
+    if stale_after <= 0 {
+        return Err(ApiError::BadRequest(format!("invalid stale_after {}", stale_after)));
+    }
+
+    let status = with_conn(pool, move |conn| {
+        let last_run = db::helpers::get_scrape_runs(conn, 1)?.pop();
+        let last_events_run =
+            db::helpers::get_last_successful_scrape(conn, &[db::helpers::RUN_EVENTS, db::helpers::RUN_FULL])?;
+        let last_links_run =
+            db::helpers::get_last_successful_scrape(conn, &[db::helpers::RUN_LINKS, db::helpers::RUN_FULL])?;
+
+        let oldest_allowed = Utc::now().naive_utc() - Duration::minutes(stale_after);
+        let is_fresh = |run: &Option<ScrapeRun>| {
+            run.as_ref()
+                .and_then(|r| r.finished_at)
+                .is_some_and(|t| t >= oldest_allowed)
+        };
+        let stale = !is_fresh(&last_events_run) || !is_fresh(&last_links_run);
+
+        Ok(ScrapeStatus {
+            last_run,
+            last_events_run,
+            last_links_run,
+            events: db::helpers::count_streams(conn)?,
+            links: db::helpers::count_links(conn)?,
+            stale,
+            stale_after_minutes: stale_after,
+        })
+    })
+    .await?;
+    let stale = status.stale;
+
+    let code = if stale {
+        Status::ServiceUnavailable
+    } else {
+        Status::Ok
+    };
+
+    Ok((code, Json(status)))
 }
 
 #[get("/leagues")]
-async fn info_get_leagues() -> Json<Vec<LeagueWithCountry>> {
-    let mut conn = db::helpers::establish_connection().unwrap();
-    let leagues = db::helpers::get_unique_leagues_with_country(&mut conn).unwrap();
+async fn info_get_leagues(pool: &State<DbPool>) -> ApiResult<Vec<LeagueWithCountry>> {
+    let leagues = with_conn(pool, |conn| Ok(db::helpers::get_unique_leagues_with_country(conn)?)).await?;
 
-    Json(leagues)
+    Ok(Json(leagues))
+}
+
+#[get("/teams")]
+async fn info_get_teams(pool: &State<DbPool>) -> ApiResult<Vec<Team>> {
+    let teams = with_conn(pool, |conn| Ok(db::helpers::get_teams(conn)?)).await?;
+
+    Ok(Json(teams))
 }
 
 #[get("/sports")]
-async fn info_get_sports() -> Json<Vec<Sport>> {
-    let sports = constants::sports::SPORTS.to_vec();
+async fn info_get_sports(catalogue: &State<Arc<Catalogue>>) -> Json<Vec<Sport>> {
+    let sports = catalogue.enabled();
 
     Json(sports)
 }
 
+/// Runs a graphql request and returns the response with a 400 status if it failed.
+/// The resolvers query the database, so the request runs on the blocking threads
+async fn execute_graphql(
+    request: GraphQLRequest,
+    schema: &Arc<Schema>,
+    pool: &DbPool,
+    catalogue: &Arc<Catalogue>,
+) -> Result<(Status, RawJson<String>), ApiError> {
+    let schema = schema.clone();
+    let context = graphql::Context {
+        pool: pool.clone(),
+        catalogue: catalogue.clone(),
+    };
 
-pub async fn run(port: u16, silent: bool) -> anyhow::Result<()> {
-    Rocket::custom(rocket::Config {
-        port,
-        log_level: if silent {
-            rocket::config::LogLevel::Off
-        } else {
-            rocket::config::LogLevel::Normal
+    let (ok, body) = spawn_blocking(move || {
+        let response = request.execute_sync(&schema, &context);
+
+        (response.is_ok(), serde_json::to_string(&response))
+    })
+    .await?;
+
+    let status = if ok {
+        Status::Ok
+    } else {
+        Status::BadRequest
+    };
+
+    let body = body.map_err(anyhow::Error::from)?;
+
+    Ok((status, RawJson(body)))
+}
+
+#[get("/graphql?<query>&<operation_name>&<variables>")]
+async fn get_graphql(
+    query: String,
+    operation_name: Option<String>,
+    variables: Option<String>,
+    schema: &State<Arc<Schema>>,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> Result<(Status, RawJson<String>), ApiError> {
+    let variables = variables
+        .map(|v| serde_json::from_str(&v))
+        .transpose()
+        .map_err(|e| ApiError::BadRequest(format!("invalid variables: {}", e)))?;
+
+    execute_graphql(GraphQLRequest::new(query, operation_name, variables), schema, pool, catalogue).await
+}
+
+#[post("/graphql", format = "json", data = "<request>")]
+async fn post_graphql(
+    request: Json<GraphQLRequest>,
+    schema: &State<Arc<Schema>>,
+    pool: &State<DbPool>,
+    catalogue: &State<Arc<Catalogue>>,
+) -> Result<(Status, RawJson<String>), ApiError> {
+    execute_graphql(request.into_inner(), schema, pool, catalogue).await
+}
+
+#[get("/graphiql")]
+async fn get_graphiql() -> RawHtml<String> {
+    RawHtml(juniper::http::graphiql::graphiql_source("/graphql", None))
+}
+
+#[catch(404)]
+fn not_found(request: &Request) -> (Status, Json<ErrorBody>) {
+    (
+        Status::NotFound,
+        Json(ErrorBody {
+            error: format!("no route for {}", request.uri()),
+        }),
+    )
+}
+
+#[catch(default)]
+fn default_catcher(status: Status, _request: &Request) -> (Status, Json<ErrorBody>) {
+    (
+        status,
+        Json(ErrorBody {
+            error: status.reason_lossy().to_lowercase(),
+        }),
+    )
+}
+
+/// Build the rocket instance with all the routes, using `pool` for the database
+///
+/// # Arguments
+/// * `pool` - The connection pool the handlers use
+/// * `catalogue` - The sports, for `/info/sports` and how long the events last
+/// * `config` - The rocket config (port, log level...)
+pub fn build(pool: DbPool, catalogue: Catalogue, config: rocket::Config) -> Rocket<Build> {
+    Rocket::custom(config)
+        .manage(pool)
+        .manage(Arc::new(catalogue))
+        .manage(Arc::new(graphql::schema()))
+        .mount(
+            "/",
+            routes![
+                get_route_desc,
+                get_version,
+                get_all_streams,
+                get_active_streams,
+                get_live_streams,
+                get_upcoming_streams,
+                get_stream_by_id,
+                get_link_history,
+                get_streams_by_sport,
+                get_streams_by_league,
+                get_streams_by_home_team,
+                get_streams_by_away_team,
+                get_streams_by_either_team,
+                get_filtered_streams,
+                search_streams,
+                get_status,
+                get_archived_streams,
+                get_graphql,
+                post_graphql,
+                get_graphiql,
+            ],
+        )
+        .mount("/info", routes![info_get_leagues, info_get_sports, info_get_teams])
+        .register("/", catchers![not_found, default_catcher])
+}
+
+pub async fn run(database_url: &str, catalogue: Catalogue, port: u16, silent: bool) -> anyhow::Result<()> {
+    let pool = db::helpers::create_pool(database_url)?;
+
+    build(
+        pool,
+        catalogue,
+        rocket::Config {
+            port,
+            log_level: if silent {
+                rocket::config::LogLevel::Off
+            } else {
+                rocket::config::LogLevel::Normal
+            },
+            ..Default::default()
         },
-        ..Default::default()
-    })
-    .mount(
-        "/",
-        routes![
-            get_route_desc,
-            get_version,
-            get_all_streams,
-            get_active_streams,
-            get_stream_by_id,
-            get_streams_by_sport,
-            get_streams_by_league,
-            get_streams_by_home_team,
-            get_streams_by_away_team,
-            get_streams_by_either_team,
-        ],
     )
-    .mount("/info", routes![info_get_leagues, info_get_sports])
     .launch()
     .await?;
 
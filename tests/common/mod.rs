@@ -1,11 +1,91 @@
 use anyhow::Error;
+use chrono::{FixedOffset, Offset, Utc};
 use diesel::SqliteConnection;
-use scraper::db::helpers::establish_test_connection;
+use scraper::{
+    db::{helpers::establish_test_connection, models::LinkCheck},
+    fetcher::Fetcher,
+    link_checker::Prober,
+};
 
 
+/// A fresh in-memory database, so tests don't step on each other or on the real database
+#[allow(dead_code)]
 pub fn create_db() -> Result<SqliteConnection, Error> {
     let test_db = establish_test_connection()?;
 
 
     Ok(test_db)
 }
+
+/// Serves saved html pages from `tests/fixtures/` instead of the live site,
+/// keyed by the url they were recorded from
+#[allow(dead_code)]
+pub struct FixtureFetcher {
+    pages: std::collections::HashMap<String, String>,
+    offset: FixedOffset,
+}
+
+#[allow(dead_code)]
+impl FixtureFetcher {
+    pub fn new(pages: &[(&str, &str)]) -> FixtureFetcher {
+        FixtureFetcher {
+            pages: pages
+                .iter()
+                .map(|(url, file)| (url.to_string(), file.to_string()))
+                .collect(),
+            offset: Utc.fix(),
+        }
+    }
+
+    /// Pretend the pages show their times in `offset`
+    pub fn with_utc_offset(mut self, offset: FixedOffset) -> FixtureFetcher {
+        self.offset = offset;
+        self
+    }
+}
+
+impl Fetcher for FixtureFetcher {
+    fn fetch_page(&self, url: &str) -> Result<String, Error> {
+        let file = self
+            .pages
+            .get(url)
+            .ok_or(anyhow::anyhow!("no fixture recorded for {}", url))?;
+
+        Ok(fixture(file))
+    }
+
+    fn utc_offset(&self) -> Result<FixedOffset, Error> {
+        Ok(self.offset)
+    }
+}
+
+/// Read a saved page from `tests/fixtures/`
+#[allow(dead_code)]
+pub fn fixture(file: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), file)).unwrap()
+}
+
+/// Answers link checks without any network, the urls in `dead` are unreachable and the others work
+#[allow(dead_code)]
+pub struct FakeProber {
+    pub dead: Vec<String>,
+    /// links the prober panics on, like a bug on an odd page would
+    pub panics: Vec<String>,
+}
+
+impl Prober for FakeProber {
+    fn probe(&self, url: &str) -> LinkCheck {
+        if self.panics.iter().any(|p| p == url) {
+            panic!("can't probe {}", url);
+        }
+
+        let dead = self.dead.iter().any(|d| d == url);
+
+        LinkCheck {
+            reachable: Some(!dead),
+            http_status: Some(if dead { 404 } else { 200 }),
+            final_url: Some(url.to_string()),
+            player_type: Some("iframe".to_string()),
+        }
+    }
+}
@@ -1,9 +1,14 @@
 use anyhow::Error;
 use chrono::NaiveDateTime;
-use diesel::{SqliteConnection};
-use scraper::db::{
-    helpers::{self, create_stream},
-    models::StreamNew,
+use diesel::{Connection, RunQueryDsl, SqliteConnection};
+use diesel_migrations::MigrationHarness;
+use scraper::{
+    constants::sports::Catalogue,
+    db::{
+        helpers::{self, create_stream},
+        models::{LinkInfo, StreamNew},
+    },
+    link_checker,
 };
 
 mod common;
@@ -19,10 +24,11 @@ fn create_new_stream(conn: &mut SqliteConnection) -> Result<(), Error> {
         away: "Chelsea",
         sport: "Football",
         start_time: NaiveDateTime::from_timestamp_opt(1612128000, 0).unwrap(),
-        stream_link: "https://www.youtube.com/watch?v=dQw4w9WgXcQ,https://www.youtube.com/watch?v=dQw4w9WgXcQ",
         country: "England",
         league: "Premier League",
         url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        home_team_id: None,
+        away_team_id: None,
     };
 
     create_stream(conn, &stream)?;
@@ -48,3 +54,338 @@ fn test_get_home_team() -> Result<(), anyhow::Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_links() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+
+    create_new_stream(&mut test_db)?;
+
+    let streams = helpers::get_streams(&mut test_db)?;
+    let event_id = streams[0].id.unwrap();
+
+    // an event without links serialises to an empty array
+    let with_links = helpers::attach_links(&mut test_db, &Catalogue::default(), streams.clone())?;
+    assert!(with_links[0].stream_link.is_empty());
+    assert_eq!(helpers::get_empty_streams(&mut test_db)?.len(), 1);
+
+    // commas in urls used to split a link in two
+    let urls = vec![
+        LinkInfo {
+            url: "https://example.com/embed?ids=1,2,3".to_string(),
+            language: Some("English".to_string()),
+            quality: Some("HD".to_string()),
+            ..Default::default()
+        },
+        LinkInfo {
+            url: "https://example.com/other".to_string(),
+            ..Default::default()
+        },
+    ];
+    let now = NaiveDateTime::from_timestamp_opt(1612128000, 0).unwrap();
+    helpers::save_links(&mut test_db, event_id, &urls, now)?;
+
+    let links = helpers::get_links(&mut test_db, event_id)?;
+    assert_eq!(links.len(), 2);
+    assert_eq!(links[0].url, "https://example.com/embed?ids=1,2,3");
+    assert_eq!(links[0].language.as_deref(), Some("English"));
+    assert_eq!(helpers::get_linked_streams(&mut test_db)?.len(), 1);
+
+    // saving again replaces the links that are no longer on the page
+    helpers::save_links(&mut test_db, event_id, &urls[1..], now)?;
+
+    let with_links = helpers::attach_links(&mut test_db, &Catalogue::default(), streams)?;
+    assert_eq!(with_links[0].stream_link, vec!["https://example.com/other".to_string()]);
+
+    Ok(())
+}
+
+#[test]
+fn test_link_history() -> Result<(), anyhow::Error> {
+    let mut test_db = test_create_stream()?;
+
+    create_new_stream(&mut test_db)?;
+
+    let kickoff = NaiveDateTime::from_timestamp_opt(1612128000, 0).unwrap();
+    let event_id = helpers::get_streams(&mut test_db)?[0].id.unwrap();
+    let link = |url: &str| LinkInfo {
+        url: url.to_string(),
+        ..Default::default()
+    };
+    let both = [link("https://example.com/a"), link("https://example.com/b")];
+    let minutes = chrono::Duration::minutes;
+
+    // b is pulled half an hour in and comes back later
+    helpers::save_links(&mut test_db, event_id, &both, kickoff - minutes(10))?;
+    helpers::save_links(&mut test_db, event_id, &both[..1], kickoff + minutes(30))?;
+    assert_eq!(helpers::get_links(&mut test_db, event_id)?.len(), 1);
+    assert_eq!(helpers::count_links(&mut test_db)?, 1);
+
+    helpers::save_links(&mut test_db, event_id, &both, kickoff + minutes(60))?;
+    helpers::save_links(&mut test_db, event_id, &both, kickoff + minutes(90))?;
+    assert_eq!(helpers::get_links(&mut test_db, event_id)?.len(), 2);
+
+    let history = helpers::get_link_history(&mut test_db, &Catalogue::default(), event_id)?.unwrap();
+    assert_eq!((history.added, history.removed, history.returned), (2, 1, 1));
+
+    let a = &history.links[0];
+    assert!(a.stable);
+    assert!(!a.pulled_mid_game);
+    assert_eq!(a.sightings.len(), 1);
+    assert_eq!(a.sightings[0].first_seen, kickoff - minutes(10));
+    assert_eq!(a.sightings[0].last_seen, kickoff + minutes(90));
+
+    let b = &history.links[1];
+    assert!(!b.stable);
+    assert!(b.pulled_mid_game);
+    assert_eq!(b.sightings.len(), 2);
+    assert_eq!(b.sightings[0].removed_at, Some(kickoff + minutes(30)));
+    assert_eq!(b.link.removed_at, None);
+
+    assert!(helpers::get_link_history(&mut test_db, &Catalogue::default(), event_id + 1)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_link_checks() -> Result<(), anyhow::Error> {
+    let mut test_db = test_create_stream()?;
+
+    create_new_stream(&mut test_db)?;
+
+    let now = NaiveDateTime::from_timestamp_opt(1612128000, 0).unwrap();
+    let event_id = helpers::get_streams(&mut test_db)?[0].id.unwrap();
+    let links: Vec<LinkInfo> = ["https://example.com/alive", "https://example.com/dead"]
+        .iter()
+        .map(|url| LinkInfo {
+            url: url.to_string(),
+            ..Default::default()
+        })
+        .collect();
+    helpers::save_links(&mut test_db, event_id, &links, now)?;
+
+    let prober = common::FakeProber {
+        dead: vec!["https://example.com/dead".to_string()],
+        panics: vec![],
+    };
+    let summary = link_checker::check_links(&mut test_db, &prober, 4, now, now)?;
+    assert_eq!((summary.checked, summary.reachable, summary.dead), (2, 1, 1));
+
+    // the dead link is left out of the api but still counts as on the page
+    let streams = helpers::get_streams(&mut test_db)?;
+    let streams = helpers::attach_links(&mut test_db, &Catalogue::default(), streams)?;
+    assert_eq!(streams[0].stream_link, vec!["https://example.com/alive".to_string()]);
+    assert_eq!(streams[0].links[0].http_status, Some(200));
+    assert_eq!(streams[0].links[0].checked_at, Some(now));
+    assert_eq!(helpers::get_links(&mut test_db, event_id)?.len(), 2);
+
+    // links checked recently are skipped
+    let summary = link_checker::check_links(&mut test_db, &prober, 4, now, now)?;
+    assert_eq!(summary.checked, 0);
+    assert_eq!(helpers::get_links_to_check(&mut test_db, now + chrono::Duration::minutes(1))?.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_link_check_panics() -> Result<(), anyhow::Error> {
+    let mut test_db = test_create_stream()?;
+
+    create_new_stream(&mut test_db)?;
+
+    let now = NaiveDateTime::from_timestamp_opt(1612128000, 0).unwrap();
+    let event_id = helpers::get_streams(&mut test_db)?[0].id.unwrap();
+    let links: Vec<LinkInfo> = ["https://example.com/1", "https://example.com/odd", "https://example.com/2"]
+        .iter()
+        .map(|url| LinkInfo {
+            url: url.to_string(),
+            ..Default::default()
+        })
+        .collect();
+    helpers::save_links(&mut test_db, event_id, &links, now)?;
+
+    let prober = common::FakeProber {
+        dead: vec![],
+        panics: vec!["https://example.com/odd".to_string()],
+    };
+
+    // the panic only loses the check of its own link
+    let summary = link_checker::check_links(&mut test_db, &prober, 2, now, now)?;
+    assert_eq!((summary.checked, summary.reachable, summary.failed), (2, 2, 1));
+
+    let unchecked = helpers::get_links_to_check(&mut test_db, now)?;
+    assert_eq!(unchecked.len(), 1);
+    assert_eq!(unchecked[0].url, "https://example.com/odd");
+
+    Ok(())
+}
+
+#[test]
+fn test_archive() -> Result<(), anyhow::Error> {
+    let mut test_db = test_create_stream()?;
+
+    create_new_stream(&mut test_db)?;
+
+    let start = NaiveDateTime::from_timestamp_opt(1612128000, 0).unwrap();
+    let event_id = helpers::get_streams(&mut test_db)?[0].id.unwrap();
+    let link = LinkInfo {
+        url: "https://example.com/embed".to_string(),
+        ..Default::default()
+    };
+    helpers::save_links(&mut test_db, event_id, &[link], start)?;
+    let catalogue = Catalogue::default();
+
+    // the game is still on an hour in, so nothing is archived yet
+    assert_eq!(helpers::archive_past_streams(&mut test_db, &catalogue, start + chrono::Duration::hours(1))?, 0);
+
+    let later = start + chrono::Duration::days(1);
+    assert_eq!(helpers::archive_past_streams(&mut test_db, &catalogue, later)?, 1);
+    assert!(helpers::get_streams(&mut test_db)?.is_empty());
+
+    // archived events keep their links and can still be found by id or date
+    let archived = helpers::get_archived_streams(&mut test_db, Some(start), Some(later))?;
+    assert_eq!(archived.len(), 1);
+    assert_eq!(archived[0].archived_at, Some(later));
+    assert_eq!(helpers::get_links(&mut test_db, event_id)?.len(), 1);
+    assert_eq!(helpers::get_streams_by_id(&mut test_db, event_id)?.len(), 1);
+    assert!(helpers::get_archived_streams(&mut test_db, Some(later), None)?.is_empty());
+
+    // purging only deletes what was archived before the given time
+    assert_eq!(helpers::purge_archived_streams(&mut test_db, later)?, 0);
+    assert_eq!(helpers::purge_archived_streams(&mut test_db, later + chrono::Duration::days(30))?, 1);
+    assert!(helpers::get_archived_streams(&mut test_db, None, None)?.is_empty());
+    assert_eq!(helpers::count_links(&mut test_db)?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_migrations_and_pruning() -> Result<(), anyhow::Error> {
+    // a raw connection skips the migrations establish_connection runs on in-memory databases
+    let mut raw = SqliteConnection::establish(":memory:")?;
+    let pending = helpers::pending_migrations(&mut raw)?;
+    assert!(!pending.is_empty());
+
+    assert_eq!(helpers::run_migrations(&mut raw)?.len(), pending.len());
+    assert!(helpers::run_migrations(&mut raw)?.is_empty());
+    assert_eq!(helpers::applied_migrations(&mut raw)?.len(), pending.len());
+
+    // migrating doesn't touch the events, pruning does
+    create_new_stream(&mut raw)?;
+    assert!(helpers::run_migrations(&mut raw)?.is_empty());
+    assert_eq!(helpers::get_streams(&mut raw)?.len(), 1);
+
+    let catalogue = Catalogue::default();
+    let retention = Some(chrono::Duration::days(30));
+    let now = NaiveDateTime::from_timestamp_opt(1612128000, 0).unwrap() + chrono::Duration::days(10);
+    let report = helpers::prune_streams(&mut raw, &catalogue, now, retention)?;
+    assert_eq!(report, helpers::PruneReport { archived: 1, purged: 0 });
+
+    let report = helpers::prune_streams(&mut raw, &catalogue, now + chrono::Duration::days(31), retention)?;
+    assert_eq!(report, helpers::PruneReport { archived: 0, purged: 1 });
+
+    Ok(())
+}
+
+#[test]
+fn test_migrated_teams_are_merged() -> Result<(), anyhow::Error> {
+    let mut raw = SqliteConnection::establish(":memory:")?;
+
+    // an event scraped before the teams existed
+    for _ in 0..3 {
+        raw.run_next_migration(helpers::MIGRATIONS).map_err(|e| anyhow::anyhow!(e))?;
+    }
+    diesel::sql_query(
+        "INSERT INTO stream (home, away, start_time, league, country, url, sport) \
+         VALUES ('Man Utd', 'MAN UTD', '2021-01-31 21:20:00', 'Premier League', 'England', 'url', 'Football')",
+    )
+    .execute(&mut raw)?;
+
+    helpers::run_migrations(&mut raw)?;
+    helpers::seed_teams(&mut raw, &scraper::teams::bundled_teams()?)?;
+
+    let united = helpers::find_team(&mut raw, "Manchester United")?.unwrap();
+    let streams = helpers::get_streams(&mut raw)?;
+    assert_eq!(streams[0].home_team_id, Some(united.id));
+    assert_eq!(streams[0].away_team_id, Some(united.id));
+    assert!(helpers::get_teams(&mut raw)?.iter().all(|t| !t.name.eq_ignore_ascii_case("man utd")));
+
+    Ok(())
+}
+
+#[test]
+fn test_memory_databases_are_isolated() -> Result<(), anyhow::Error> {
+    let mut first = common::create_db()?;
+    let mut second = common::create_db()?;
+
+    create_new_stream(&mut first)?;
+
+    assert_eq!(helpers::get_streams(&mut first)?.len(), 1);
+    assert_eq!(helpers::get_streams(&mut second)?.len(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_filtered_streams() -> Result<(), anyhow::Error> {
+    let mut test_db = test_create_stream()?;
+
+    create_new_stream(&mut test_db)?;
+
+    let start = NaiveDateTime::from_timestamp_opt(1612128000, 0).unwrap();
+
+    let filter = helpers::StreamFilter::new()
+        .sport("Football")
+        .country("England")
+        .starts_between(start - chrono::Duration::hours(1), start + chrono::Duration::hours(2));
+    assert_eq!(helpers::get_filtered_streams(&mut test_db, &filter)?.len(), 1);
+
+    let with_links = filter.clone().has_links(true);
+    assert_eq!(helpers::get_filtered_streams(&mut test_db, &with_links)?.len(), 0);
+
+    let other_team = filter.team("Liverpool");
+    assert_eq!(helpers::get_filtered_streams(&mut test_db, &other_team)?.len(), 0);
+
+    let before = start - chrono::Duration::hours(1);
+    assert_eq!(helpers::get_next_kickoff(&mut test_db, before)?, Some(start));
+    assert_eq!(helpers::get_next_kickoff(&mut test_db, start)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_team_aliases() -> Result<(), anyhow::Error> {
+    let mut test_db = test_create_stream()?;
+
+    helpers::seed_teams(&mut test_db, &scraper::teams::bundled_teams()?)?;
+
+    let united = helpers::resolve_team(&mut test_db, "Manchester United")?;
+    assert!(united.is_some());
+    assert_eq!(helpers::resolve_team(&mut test_db, "MAN UTD")?, united);
+
+    // the "а" is cyrillic
+    assert_eq!(helpers::resolve_team(&mut test_db, "Mаn United")?, united);
+
+    let stream = StreamNew {
+        home: "Man Utd",
+        away: "Chelsea",
+        sport: "Football",
+        start_time: NaiveDateTime::from_timestamp_opt(1612128000, 0).unwrap(),
+        country: "England",
+        league: "Premier League",
+        url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        home_team_id: united,
+        away_team_id: helpers::resolve_team(&mut test_db, "Chelsea")?,
+    };
+    create_stream(&mut test_db, &stream)?;
+
+    assert_eq!(helpers::get_streams_by_home_team(&mut test_db, "Manchester United".to_string())?.len(), 1);
+    assert_eq!(helpers::get_streams_by_either_team(&mut test_db, "man utd".to_string())?.len(), 1);
+
+    // a new alias merges the team that was created from that spelling
+    helpers::add_team_alias(&mut test_db, "Chelsea FC", "Chelsea")?;
+    assert_eq!(helpers::get_streams_by_away_team(&mut test_db, "Chelsea FC".to_string())?.len(), 1);
+    assert!(helpers::get_teams(&mut test_db)?.iter().all(|t| t.name != "Chelsea"));
+
+    Ok(())
+}
@@ -0,0 +1,429 @@
+use std::cell::Cell;
+
+use scraper::{
+    constants::sports::{self, Catalogue},
+    db::{helpers, models::FailureKind},
+    fetcher::{select_element, Fetcher},
+    query_selectors::LinkScrapeError,
+    retry::RetryPolicy,
+    scrape,
+};
+
+mod common;
+
+const SCHEDULE_URL: &str = "https://reddit3.sportshub.stream/";
+const EVENT_URL: &str = "https://reddit3.sportshub.stream/event/tоttеnhаm_brеntfоrd_187734226/";
+
+
+/// the fixtures were recorded on the 31st of January 2024
+fn scrape_start() -> chrono::DateTime<chrono::Utc> {
+    chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2024, 1, 31, 12, 0, 0).unwrap()
+}
+
+/// retry straight away, the tests don't need to wait
+fn no_wait() -> RetryPolicy {
+    RetryPolicy {
+        initial_delay_ms: 0,
+        ..Default::default()
+    }
+}
+
+fn fixture_fetcher() -> common::FixtureFetcher {
+    common::FixtureFetcher::new(&[
+        (SCHEDULE_URL, "schedule_football.html"),
+        (EVENT_URL, "event_tottenham_brentford.html"),
+    ])
+}
+
+#[test]
+fn test_today_games_from_fixture() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+
+    scrape::today_games(&fixture_fetcher(), &mut test_db, &sports::SOCCER, &no_wait(), scrape_start())?;
+
+    let streams = helpers::get_streams(&mut test_db)?;
+    assert_eq!(streams.len(), 3);
+
+    let spurs = &streams[0];
+    assert_eq!(spurs.home, "Tottenham");
+    assert_eq!(spurs.away, "Brentford");
+    assert_eq!(spurs.league, "Premier League");
+    assert_eq!(spurs.country, "england");
+    assert_eq!(spurs.sport, "Football");
+    assert_eq!(spurs.url, EVENT_URL);
+    assert_eq!(spurs.start_time.format("%Y-%m-%d %H:%M").to_string(), "2024-01-31 19:30");
+    assert!(spurs.home_team_id.is_some());
+    assert_eq!(spurs.home_team_id, helpers::find_team(&mut test_db, "tottenham")?.map(|t| t.id));
+
+    let ypiranga = &streams[1];
+    assert_eq!(ypiranga.home, "Ypiranga RS");
+    assert_eq!(ypiranga.away, "Novo Hamburgo");
+    assert_eq!(ypiranga.league, "Brazilian Campeonato Gaucho");
+    assert_eq!(ypiranga.country, "brazil");
+
+    // scraping the same page twice doesn't duplicate the games
+    scrape::today_games(&fixture_fetcher(), &mut test_db, &sports::SOCCER, &no_wait(), scrape_start())?;
+    assert_eq!(helpers::get_streams(&mut test_db)?.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_links_from_fixture() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+    let fetcher = fixture_fetcher();
+
+    scrape::today_games(&fetcher, &mut test_db, &sports::SOCCER, &no_wait(), scrape_start())?;
+
+    let table = select_element(&fetcher.fetch_page(EVENT_URL)?, ".lnktbj")?;
+    scrape::parse_links(&mut test_db, EVENT_URL, &table)?;
+
+    let linked = helpers::get_linked_streams(&mut test_db)?;
+    let linked = helpers::attach_links(&mut test_db, &Catalogue::default(), linked)?;
+    assert_eq!(linked.len(), 1);
+    assert_eq!(linked[0].stream.home, "Tottenham");
+
+    // the row without a link is skipped, the &amp; in the href is decoded
+    assert_eq!(
+        linked[0].stream_link,
+        vec![
+            "https://streams.example.com/embed/spurs-brentford?ids=1,2&lang=en",
+            "https://futbol.example.org/live/187734226",
+            "acestream://3f8a1c2b9d",
+        ]
+    );
+
+    let links = &linked[0].links;
+    assert_eq!(links[0].streamer.as_deref(), Some("SoccerStreams"));
+    assert_eq!(links[0].language.as_deref(), Some("English"));
+    assert_eq!(links[0].quality.as_deref(), Some("HD 2500kbps"));
+    assert_eq!(links[0].ad_count, Some(2));
+    assert_eq!(links[1].language.as_deref(), Some("Spanish"));
+    assert_eq!(links[1].ad_count, Some(4));
+    assert_eq!(links[2].channel_type.as_deref(), Some("Acestream"));
+
+    // the other games have no links yet
+    assert_eq!(helpers::get_empty_streams(&mut test_db)?.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_links_are_scraped_again() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+    let fetcher = fixture_fetcher();
+    let catalogue = Catalogue::default();
+
+    scrape::today_games(&fetcher, &mut test_db, &sports::SOCCER, &no_wait(), scrape_start())?;
+
+    let table = select_element(&fetcher.fetch_page(EVENT_URL)?, ".lnktbj")?;
+    scrape::parse_links(&mut test_db, EVENT_URL, &table)?;
+
+    let event = helpers::get_linked_streams(&mut test_db)?.remove(0);
+    let event_id = event.id.unwrap();
+
+    // the game still has to be scraped before and during the game, not once it's over
+    let before = event.start_time - chrono::Duration::hours(1);
+    let to_update = helpers::get_streams_to_update(&mut test_db, &catalogue, before)?;
+    assert!(to_update.iter().any(|s| s.id == Some(event_id)));
+    assert_eq!(to_update.len(), 3);
+
+    let after = event.start_time + chrono::Duration::days(1);
+    let to_update = helpers::get_streams_to_update(&mut test_db, &catalogue, after)?;
+    assert!(!to_update.iter().any(|s| s.id == Some(event_id)));
+    assert_eq!(to_update.len(), 2);
+
+    // the spanish stream is pulled before the next scrape
+    let spanish = table.find("<tr>\n\t\t\t\t\t<td>FutbolLive").unwrap();
+    let end = spanish + table[spanish..].find("</tr>").unwrap() + "</tr>".len();
+    let pulled = format!("{}{}", &table[..spanish], &table[end..]);
+    assert_eq!(scrape::parse_links(&mut test_db, EVENT_URL, &pulled)?, 2);
+
+    let history = helpers::get_link_history(&mut test_db, &catalogue, event_id)?.unwrap();
+    assert_eq!((history.added, history.removed), (3, 1));
+    let removed: Vec<&str> = history
+        .links
+        .iter()
+        .filter(|l| l.link.removed_at.is_some())
+        .map(|l| l.link.url.as_str())
+        .collect();
+    assert_eq!(removed, vec!["https://futbol.example.org/live/187734226"]);
+    assert_eq!(helpers::get_links(&mut test_db, event_id)?.len(), 2);
+
+    // a page without the link table lost all its links
+    assert_eq!(scrape::save_event_links(&mut test_db, EVENT_URL, &[])?, 0);
+    assert!(helpers::get_links(&mut test_db, event_id)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_times_are_stored_in_utc() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+
+    // the page shows 19:30 in Paris, which is 18:30 utc
+    let paris = chrono::FixedOffset::east_opt(3600).unwrap();
+    scrape::today_games(
+        &fixture_fetcher().with_utc_offset(paris),
+        &mut test_db,
+        &sports::SOCCER,
+        &no_wait(),
+        scrape_start(),
+    )?;
+
+    let streams = helpers::get_streams(&mut test_db)?;
+    assert_eq!(streams[0].start_time.format("%Y-%m-%d %H:%M").to_string(), "2024-01-31 18:30");
+
+    let offset = helpers::get_meta(&mut test_db, helpers::META_UTC_OFFSET)?;
+    assert_eq!(offset.as_deref(), Some("+01:00"));
+
+    Ok(())
+}
+
+#[test]
+fn test_scrapes_are_recorded() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+
+    let soccer = [sports::SOCCER];
+    let report = scrape::scrape_sports(&fixture_fetcher(), &mut test_db, &soccer, &no_wait(), None, scrape_start())?;
+    assert_eq!(report.events_found(), 3);
+    assert!(report.is_success());
+
+    let runs = helpers::get_scrape_runs(&mut test_db, 10)?;
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].kind, helpers::RUN_EVENTS);
+    assert_eq!(runs[0].events_found, 3);
+    assert_eq!(runs[0].sports().get("Football"), Some(&3));
+    assert!(runs[0].finished_at.is_some());
+    assert!(runs[0].is_success());
+
+    // there is no page recorded for basketball, the run is saved with the error
+    let basketball = [sports::BASKETBALL];
+    let report =
+        scrape::scrape_sports(&fixture_fetcher(), &mut test_db, &basketball, &no_wait(), None, scrape_start())?;
+    assert!(!report.is_success());
+
+    let runs = helpers::get_scrape_runs(&mut test_db, 10)?;
+    assert_eq!(runs.len(), 2);
+    assert!(!runs[0].is_success());
+    assert_eq!(runs[0].errors()[0].sport.as_deref(), Some("Basketball"));
+
+    let last = helpers::get_last_successful_scrape(&mut test_db, &[helpers::RUN_EVENTS])?;
+    assert_eq!(last.map(|r| r.id), Some(runs[1].id));
+
+    Ok(())
+}
+
+#[test]
+fn test_failing_sport_doesnt_stop_the_others() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+
+    // basketball has no page recorded, like a dead subdomain
+    let sports = [sports::BASKETBALL, sports::SOCCER];
+    let report = scrape::scrape_sports(&fixture_fetcher(), &mut test_db, &sports, &no_wait(), None, scrape_start())?;
+
+    assert_eq!(report.sports.get("Football"), Some(&3));
+    assert_eq!(report.sports.get("Basketball"), None);
+    assert_eq!(helpers::get_streams(&mut test_db)?.len(), 3);
+
+    assert_eq!(report.errors.len(), 1);
+    let failure = &report.errors[0];
+    assert_eq!(failure.kind, FailureKind::Sport);
+    assert_eq!(failure.sport.as_deref(), Some("Basketball"));
+    assert_eq!(failure.url.as_deref(), Some(&*sports::BASKETBALL.url));
+    assert_eq!(failure.attempts, 3);
+
+    // losing a sport still counts as a successful run
+    assert!(report.is_success());
+
+    Ok(())
+}
+
+#[test]
+fn test_sports_after_a_failing_one_are_scraped() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+
+    // the sport after the dead one has to be scraped too, not only the ones before it
+    let rugby = sports::Sport::new("Rugby", SCHEDULE_URL);
+    let sports = [sports::SOCCER, sports::BASKETBALL, rugby];
+    let report = scrape::scrape_sports(&fixture_fetcher(), &mut test_db, &sports, &no_wait(), None, scrape_start())?;
+
+    assert_eq!(report.sports.get("Football"), Some(&3));
+    assert_eq!(report.sports.get("Rugby"), Some(&3));
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].sport.as_deref(), Some("Basketball"));
+
+    Ok(())
+}
+
+#[test]
+fn test_broken_game_is_skipped() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+
+    let html = common::fixture("schedule_football.html").replace("31st January at 19:30", "sometime");
+    let schedule = select_element(&html, "#sports-shedule")?;
+    let offset = chrono::FixedOffset::east_opt(0).unwrap();
+
+    let report = scrape::parse_schedule(&mut test_db, "Football", &schedule, offset, scrape_start())?;
+
+    assert_eq!(report.sports.get("Football"), Some(&2));
+    assert_eq!(helpers::get_streams(&mut test_db)?.len(), 2);
+
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].kind, FailureKind::Game);
+    assert_eq!(report.errors[0].url.as_deref(), Some(EVENT_URL));
+
+    Ok(())
+}
+
+/// Times out a few times before answering, like a slow mirror
+struct FlakyFetcher {
+    failures: Cell<u32>,
+    inner: common::FixtureFetcher,
+}
+
+impl Fetcher for FlakyFetcher {
+    fn fetch_page(&self, url: &str) -> Result<String, anyhow::Error> {
+        if self.failures.get() > 0 {
+            self.failures.set(self.failures.get() - 1);
+            anyhow::bail!("timed out waiting for {}", url);
+        }
+
+        self.inner.fetch_page(url)
+    }
+}
+
+#[test]
+fn test_sport_page_is_retried() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+
+    let fetcher = FlakyFetcher {
+        failures: Cell::new(2),
+        inner: fixture_fetcher(),
+    };
+    let report = scrape::today_games(&fetcher, &mut test_db, &sports::SOCCER, &no_wait(), scrape_start())?;
+    assert_eq!(report.sports.get("Football"), Some(&3));
+
+    // once it runs out of attempts the sport fails
+    let fetcher = FlakyFetcher {
+        failures: Cell::new(2),
+        inner: fixture_fetcher(),
+    };
+    let retry = RetryPolicy {
+        attempts: 2,
+        ..no_wait()
+    };
+    assert!(scrape::today_games(&fetcher, &mut test_db, &sports::SOCCER, &retry, scrape_start()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_failing_pages_dont_stop_the_workers() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+    scrape::today_games(&fixture_fetcher(), &mut test_db, &sports::SOCCER, &no_wait(), scrape_start())?;
+    let streams = helpers::get_streams(&mut test_db)?;
+
+    // every worker counts the pages it opened
+    let report = scrape::scrape_pages(streams, vec![0; 2], |opened: &mut usize, url| {
+        *opened += 1;
+
+        if url.contains("ypiranga") {
+            Err(LinkScrapeError::InvalidUrl(url.to_string()))
+        } else if url.contains("real_madrid") {
+            panic!("odd anchor on {}", url)
+        } else {
+            Ok(3)
+        }
+    })?;
+
+    assert_eq!(report.links_found, 3);
+    assert_eq!(report.errors.len(), 2);
+    assert!(report.errors.iter().all(|e| e.kind == FailureKind::Links));
+
+    let panicked = report
+        .errors
+        .iter()
+        .find(|e| e.url.as_deref().is_some_and(|u| u.contains("real_madrid")))
+        .unwrap();
+    assert_eq!(panicked.sport.as_deref(), Some("Football"));
+    assert!(panicked.error.contains("odd anchor"));
+
+    Ok(())
+}
+
+#[test]
+fn test_dead_mirror_fails_over() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+
+    // the configured url is dead, the page moved to a mirror
+    let mut soccer = sports::SOCCER.clone();
+    soccer.url = "https://reddit2.sportshub.stream/".into();
+    soccer.mirrors = vec![SCHEDULE_URL.to_string()];
+
+    let report = scrape::today_games(&fixture_fetcher(), &mut test_db, &soccer, &no_wait(), scrape_start())?;
+    assert_eq!(report.sports.get("Football"), Some(&3));
+
+    let working = helpers::get_working_mirror(&mut test_db, "Football")?;
+    assert_eq!(working.as_deref(), Some(SCHEDULE_URL));
+
+    let health = helpers::get_mirror_health(&mut test_db, Some("Football"))?;
+    let dead = health.iter().find(|m| m.url == soccer.url).unwrap();
+    assert_eq!((dead.failures, dead.consecutive_failures), (1, 1));
+    assert!(dead.last_error.is_some());
+
+    let alive = health.iter().find(|m| m.url == SCHEDULE_URL).unwrap();
+    assert_eq!((alive.successes, alive.failures), (1, 0));
+
+    // every mirror failing fails the sport, with the attempts on all of them
+    soccer.mirrors = vec!["https://reddit4.sportshub.stream/".to_string()];
+    let nothing = common::FixtureFetcher::new(&[]);
+    let error = scrape::today_games(&nothing, &mut test_db, &soccer, &no_wait(), scrape_start()).unwrap_err();
+    assert_eq!(error.downcast_ref::<scraper::retry::GaveUp>().map(|e| e.attempts), Some(9));
+
+    Ok(())
+}
+
+#[test]
+fn test_mirrors_are_discovered() -> Result<(), anyhow::Error> {
+    let mut test_db = common::create_db()?;
+
+    const LANDING_PAGE: &str = "https://sportshub.stream/";
+    let fetcher = common::FixtureFetcher::new(&[(LANDING_PAGE, "landing.html")]);
+
+    // the configured mirror is dead, the page lives on the one linked from the landing page
+    let sports = [sports::SOCCER, sports::BASKETBALL];
+    let landing_page = Some(LANDING_PAGE);
+    let report = scrape::scrape_sports(&fetcher, &mut test_db, &sports, &no_wait(), landing_page, scrape_start())?;
+    assert_eq!(report.errors.len(), 2);
+
+    let discovered: Vec<_> = helpers::get_mirror_health(&mut test_db, None)?
+        .into_iter()
+        .filter(|m| m.discovered)
+        .map(|m| (m.sport, m.url))
+        .collect();
+
+    assert_eq!(
+        discovered,
+        vec![
+            ("Basketball".to_string(), "https://basketball3.sportshub.stream/".to_string()),
+            ("Football".to_string(), "https://reddit5.sportshub.stream/".to_string()),
+        ]
+    );
+
+    // next time the discovered mirror is tried first
+    let fetcher = common::FixtureFetcher::new(&[("https://reddit5.sportshub.stream/", "schedule_football.html")]);
+    let report = scrape::today_games(&fetcher, &mut test_db, &sports::SOCCER, &no_wait(), scrape_start())?;
+    assert_eq!(report.sports.get("Football"), Some(&3));
+
+    let working = helpers::get_working_mirror(&mut test_db, "Football")?;
+    assert_eq!(working.as_deref(), Some("https://reddit5.sportshub.stream/"));
+
+    // a landing page that doesn't load is reported, the sports are still scraped
+    let soccer = [sports::SOCCER];
+    let report = scrape::scrape_sports(&fetcher, &mut test_db, &soccer, &no_wait(), landing_page, scrape_start())?;
+    assert_eq!(report.sports.get("Football"), Some(&3));
+    assert_eq!(report.errors[0].kind, FailureKind::Discovery);
+
+    Ok(())
+}
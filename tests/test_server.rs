@@ -0,0 +1,365 @@
+use chrono::NaiveDateTime;
+use rocket::{http::Status, local::blocking::Client};
+use scraper::{
+    config::MEMORY_DATABASE,
+    constants::sports::{Catalogue, Sport},
+    db::{
+        helpers::{self, DbPool},
+        models::{LinkInfo, ScrapeReport, StreamNew},
+    },
+    web_server_routes,
+};
+
+
+fn test_client() -> Result<(Client, DbPool), anyhow::Error> {
+    let pool = helpers::create_pool(MEMORY_DATABASE)?;
+
+    helpers::create_stream(
+        &mut *pool.get()?,
+        &StreamNew {
+            home: "Arsenal",
+            away: "Chelsea",
+            sport: "Football",
+            start_time: NaiveDateTime::from_timestamp_opt(1612128000, 0).unwrap(),
+            country: "England",
+            league: "Premier League",
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            home_team_id: None,
+            away_team_id: None,
+        },
+    )?;
+
+    let client = Client::tracked(web_server_routes::build(
+        pool.clone(),
+        Catalogue::default(),
+        rocket::Config::debug_default(),
+    ))?;
+
+    Ok((client, pool))
+}
+
+#[test]
+fn test_all_streams() -> Result<(), anyhow::Error> {
+    let (client, _pool) = test_client()?;
+
+    let response = client.get("/all").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body[0]["home"], "Arsenal");
+    assert_eq!(body[0]["stream_link"], serde_json::json!([]));
+
+    Ok(())
+}
+
+#[test]
+fn test_missing_id_is_json_404() -> Result<(), anyhow::Error> {
+    let (client, _pool) = test_client()?;
+
+    let response = client.get("/id/12345").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["error"], "no event with id 12345");
+
+    let response = client.get("/does/not/exist").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    assert!(response.into_string().unwrap().contains("\"error\""));
+
+    Ok(())
+}
+
+#[test]
+fn test_graphql_filters_and_fields() -> Result<(), anyhow::Error> {
+    let (client, _pool) = test_client()?;
+
+    let response = client
+        .post("/graphql")
+        .header(rocket::http::ContentType::JSON)
+        .body(r#"{"query": "{ streams(country: \"England\", team: \"Chelsea\") { home league links { url } } }"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(
+        body["data"]["streams"],
+        serde_json::json!([{"home": "Arsenal", "league": "Premier League", "links": []}])
+    );
+
+    let response = client
+        .get("/graphql?query=%7Bstreams(sport:%22Tennis%22)%7Bid%7D%7D")
+        .dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["data"]["streams"], serde_json::json!([]));
+
+    // the page size is checked like on /streams
+    for query in ["%7Bstreams(limit:-1)%7Bid%7D%7D", "%7Bstreams(limit:1000)%7Bid%7D%7D"] {
+        let response = client.get(format!("/graphql?query={}", query)).dispatch();
+        let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+        assert_eq!(body["data"], serde_json::Value::Null);
+        assert!(body["errors"][0]["message"].as_str().unwrap().starts_with("invalid limit"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_streams_filters_and_pages() -> Result<(), anyhow::Error> {
+    let (client, pool) = test_client()?;
+
+    helpers::create_stream(
+        &mut *pool.get()?,
+        &StreamNew {
+            home: "Liverpool",
+            away: "Everton",
+            sport: "Football",
+            start_time: NaiveDateTime::from_timestamp_opt(1612131600, 0).unwrap(),
+            country: "England",
+            league: "Premier League",
+            url: "https://www.youtube.com/watch?v=2",
+            home_team_id: None,
+            away_team_id: None,
+        },
+    )?;
+
+    let response = client.get("/streams?country=England&limit=1").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["streams"][0]["home"], "Arsenal");
+    assert_eq!(body["next_offset"], 1);
+
+    let cursor = body["next_cursor"].as_str().unwrap().to_owned();
+    let response = client.get(format!("/streams?country=England&limit=1&cursor={}", cursor)).dispatch();
+
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["streams"][0]["home"], "Liverpool");
+    assert_eq!(body["next_cursor"], serde_json::Value::Null);
+
+    let response = client.get("/streams?sort=desc&has_links=false").dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["streams"][0]["home"], "Liverpool");
+    assert_eq!(body["streams"].as_array().unwrap().len(), 2);
+
+    let response = client.get("/streams?has_links=true").dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["streams"], serde_json::json!([]));
+
+    let response = client.get("/streams?sort=sideways").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let response = client.get("/streams?cursor=nope").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    // within only narrows down an explicit to
+    let kickoff = chrono::Utc::now().naive_utc() + chrono::Duration::minutes(30);
+    helpers::create_stream(
+        &mut *pool.get()?,
+        &StreamNew {
+            home: "Brentford",
+            away: "Fulham",
+            sport: "Football",
+            start_time: kickoff,
+            country: "England",
+            league: "Premier League",
+            url: "https://www.youtube.com/watch?v=3",
+            home_team_id: None,
+            away_team_id: None,
+        },
+    )?;
+
+    let response = client.get("/streams?within=60").dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["streams"][0]["home"], "Brentford");
+
+    let to = (kickoff - chrono::Duration::minutes(20)).timestamp();
+    let response = client.get(format!("/streams?within=60&to={}", to)).dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["streams"], serde_json::json!([]));
+
+    // the pages asked for by cursor only go on by cursor
+    let response = client.get(format!("/streams?country=England&limit=1&cursor={}", cursor)).dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["streams"][0]["home"], "Liverpool");
+    assert!(body["next_cursor"].is_string());
+    assert_eq!(body["next_offset"], serde_json::Value::Null);
+
+    let response = client.get(format!("/streams?limit=1&offset=1&cursor={}", cursor)).dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    Ok(())
+}
+
+#[test]
+fn test_search() -> Result<(), anyhow::Error> {
+    let (client, _pool) = test_client()?;
+
+    // the "а" and "е" are cyrillic
+    let response = client.get("/search?q=%D0%B0rs%D0%B5n%D0%B0l").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body[0]["home"], "Arsenal");
+
+    let response = client.get("/search?q=CHELSEA%20fc").dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body[0]["away"], "Chelsea");
+
+    let response = client.get("/search?q=Liverpool").dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body, serde_json::json!([]));
+
+    let response = client.get("/search?q=%20").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    Ok(())
+}
+
+#[test]
+fn test_live_and_upcoming() -> Result<(), anyhow::Error> {
+    let (client, pool) = test_client()?;
+
+    let now = chrono::Utc::now().naive_utc();
+
+    // golf lasts longer than football, so only the golf started 3 hours ago is still live
+    for (sport, start_time, url) in [
+        ("Golf", now - chrono::Duration::hours(3), "https://example.com/golf"),
+        ("Football", now - chrono::Duration::hours(3), "https://example.com/football"),
+        ("Football", now + chrono::Duration::hours(1), "https://example.com/later"),
+    ] {
+        helpers::create_stream(
+            &mut *pool.get()?,
+            &StreamNew {
+                home: "Home",
+                away: "Away",
+                sport,
+                start_time,
+                country: "World",
+                league: "League",
+                url,
+                home_team_id: None,
+                away_team_id: None,
+            },
+        )?;
+    }
+
+    let response = client.get("/live").dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["sport"], "Golf");
+    assert_eq!(body[0]["status"], "live");
+
+    let response = client.get("/upcoming").dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["url"], "https://example.com/later");
+    assert_eq!(body[0]["status"], "upcoming");
+
+    Ok(())
+}
+
+#[test]
+fn test_sports_come_from_the_catalogue() -> Result<(), anyhow::Error> {
+    let pool = helpers::create_pool(MEMORY_DATABASE)?;
+    let mut darts = Sport::new("Darts", "https://darts.sportshub.stream/");
+    darts.enabled = false;
+    let catalogue = Catalogue::new(vec![Sport::new("Kabaddi", "https://kabaddi.sportshub.stream/").lasting(50), darts]);
+
+    let client = Client::tracked(web_server_routes::build(pool, catalogue, rocket::Config::debug_default()))?;
+
+    let response = client.get("/info/sports").dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["name"], "Kabaddi");
+    assert_eq!(body[0]["duration"], 50);
+
+    let response = client.get("/graphql?query=%7Bsports%7Bname%7D%7D").dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["data"]["sports"], serde_json::json!([{"name": "Kabaddi"}]));
+
+    Ok(())
+}
+
+#[test]
+fn test_archive() -> Result<(), anyhow::Error> {
+    let (client, pool) = test_client()?;
+
+    helpers::archive_all_streams(&mut *pool.get()?, chrono::Utc::now().naive_utc())?;
+
+    let response = client.get("/all").dispatch();
+    assert_eq!(response.into_string().unwrap(), "[]");
+
+    let response = client.get("/archive?from=1612000000&to=1613000000").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body[0]["home"], "Arsenal");
+    assert!(body[0]["archived_at"].is_i64());
+
+    let response = client.get("/archive?from=1613000000").dispatch();
+    assert_eq!(response.into_string().unwrap(), "[]");
+
+    Ok(())
+}
+
+#[test]
+fn test_link_history() -> Result<(), anyhow::Error> {
+    let (client, pool) = test_client()?;
+
+    let link = LinkInfo {
+        url: "https://example.com/a".to_string(),
+        ..Default::default()
+    };
+    let start = NaiveDateTime::from_timestamp_opt(1612128000, 0).unwrap();
+    helpers::save_links(&mut *pool.get()?, 1, &[link], start)?;
+    helpers::save_links(&mut *pool.get()?, 1, &[], start + chrono::Duration::minutes(30))?;
+
+    let response = client.get("/id/1/links").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["removed"], 1);
+    assert_eq!(body["links"][0]["pulled_mid_game"], true);
+    assert_eq!(body["links"][0]["removed_at"], 1612129800);
+    assert_eq!(body["links"][0]["sightings"][0]["first_seen"], 1612128000);
+
+    // the pulled link isn't shown with the event anymore
+    let response = client.get("/id/1").dispatch();
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body[0]["stream_link"], serde_json::json!([]));
+
+    assert_eq!(client.get("/id/2/links").dispatch().status(), Status::NotFound);
+
+    Ok(())
+}
+
+#[test]
+fn test_status() -> Result<(), anyhow::Error> {
+    let (client, pool) = test_client()?;
+
+    // nothing was ever scraped
+    let response = client.get("/status").dispatch();
+    assert_eq!(response.status(), Status::ServiceUnavailable);
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["stale"], true);
+    assert_eq!(body["events"], 1);
+    assert_eq!(body["last_run"], serde_json::Value::Null);
+
+    let now = chrono::Utc::now().naive_utc();
+    let run = helpers::start_scrape_run(&mut *pool.get()?, helpers::RUN_FULL, now)?;
+    let report = ScrapeReport {
+        sports: [("Football".to_owned(), 1)].into(),
+        ..Default::default()
+    };
+    helpers::finish_scrape_run(&mut *pool.get()?, run, &report, now)?;
+
+    let response = client.get("/status").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap())?;
+    assert_eq!(body["stale"], false);
+    assert_eq!(body["last_run"]["kind"], "full");
+    assert_eq!(body["last_run"]["sports"]["Football"], 1);
+    assert_eq!(body["last_events_run"]["id"], run);
+
+    assert_eq!(client.get("/status?stale_after=0").dispatch().status(), Status::BadRequest);
+
+    Ok(())
+}